@@ -58,6 +58,13 @@ typedef struct _EekGtkKeyboardPrivate
     GdkEventSequence *sequence; // unowned reference
     LfbEvent *event;
 
+    /// Fires when the last touch was held down long enough
+    guint long_press_timeout;
+    /// Event time of the last touch
+    guint32 press_time;
+    /// When the last touch happened, on the monotonic clock
+    gint64 press_monotonic;
+
     gulong kb_signal;
 } EekGtkKeyboardPrivate;
 
@@ -109,12 +116,15 @@ eek_gtk_keyboard_real_draw (GtkWidget *self,
                     priv->keyboard,
                     pcontext);
 
-        set_allocation_size (keyboard, priv->keyboard->layout,
-            allocation.width, allocation.height);
         eek_renderer_set_scale_factor (priv->renderer,
                                        gtk_widget_get_scale_factor (self));
     }
 
+    // The placement depends on settings like the one-handed mode,
+    // which may change without the allocation changing.
+    set_allocation_size (keyboard, priv->keyboard->layout,
+        allocation.width, allocation.height);
+
     eek_renderer_render_keyboard (priv->renderer, priv->render_geometry,
         priv->submission, cr, priv->keyboard);
     return FALSE;
@@ -151,6 +161,31 @@ on_event_triggered (LfbEvent      *event,
     }
 }
 
+static void cancel_long_press(EekGtkKeyboardPrivate *priv)
+{
+    if (priv->long_press_timeout) {
+        g_source_remove(priv->long_press_timeout);
+        priv->long_press_timeout = 0;
+    }
+}
+
+static gboolean on_long_press(gpointer data)
+{
+    EekGtkKeyboard *self = EEK_GTK_KEYBOARD(data);
+    EekGtkKeyboardPrivate *priv = eek_gtk_keyboard_get_instance_private (self);
+    priv->long_press_timeout = 0;
+    if (priv->keyboard) {
+        // Timers carry no event time, so it's estimated from the press.
+        gint64 elapsed_ms = (g_get_monotonic_time() - priv->press_monotonic) / 1000;
+        squeek_layout_long_press(priv->keyboard->layout,
+                                 priv->submission,
+                                 priv->render_geometry.widget_to_layout,
+                                 priv->press_time + (guint32)elapsed_ms,
+                                 priv->popover, priv->state_manager, self);
+    }
+    return G_SOURCE_REMOVE;
+}
+
 static void depress(EekGtkKeyboard *self,
                     gdouble x, gdouble y, guint32 time)
 {
@@ -161,6 +196,12 @@ static void depress(EekGtkKeyboard *self,
     squeek_layout_depress(priv->keyboard->layout,
                           priv->submission,
                           x, y, priv->render_geometry.widget_to_layout, time, self);
+
+    cancel_long_press(priv);
+    priv->press_time = time;
+    priv->press_monotonic = g_get_monotonic_time();
+    priv->long_press_timeout = g_timeout_add(squeek_layout_get_long_press_ms(),
+                                             on_long_press, self);
 }
 
 static void drag(EekGtkKeyboard *self,
@@ -179,6 +220,7 @@ static void drag(EekGtkKeyboard *self,
 static void release(EekGtkKeyboard *self, guint32 time)
 {
     EekGtkKeyboardPrivate *priv = eek_gtk_keyboard_get_instance_private (self);
+    cancel_long_press(priv);
     if (!priv->keyboard) {
         return;
     }
@@ -270,6 +312,7 @@ eek_gtk_keyboard_real_unmap (GtkWidget *self)
     EekGtkKeyboardPrivate *priv =
         eek_gtk_keyboard_get_instance_private (EEK_GTK_KEYBOARD (self));
 
+    cancel_long_press(priv);
     if (priv->keyboard) {
         squeek_layout_release_all_only(
             priv->keyboard->layout,
@@ -307,6 +350,8 @@ eek_gtk_keyboard_dispose (GObject *object)
         priv->kb_signal = 0;
     }
 
+    cancel_long_press(priv);
+
     if (priv->renderer) {
         eek_renderer_free(priv->renderer);
         priv->renderer = NULL;
@@ -31,7 +31,8 @@
 
 /* eek-keyboard-drawing.c */
 static void render_button_label (cairo_t *cr, GtkStyleContext *ctx,
-                                                const gchar *label, EekBounds bounds);
+                                                const gchar *label, EekBounds bounds,
+                                                double text_scale);
 
 static void
 render_outline (cairo_t     *cr,
@@ -69,7 +70,8 @@ void eek_render_button_in_context(uint32_t scale_factor,
                                      GtkStyleContext *ctx,
                                      EekBounds bounds,
                                      const char *icon_name,
-                                     const gchar *label) {
+                                     const gchar *label,
+                                     double text_scale) {
     /* blank background */
     cairo_set_source_rgba (cr, 0.0, 0.0, 0.0, 0.0);
     cairo_paint (cr);
@@ -109,7 +111,7 @@ void eek_render_button_in_context(uint32_t scale_factor,
     }
 
     if (label) {
-        render_button_label (cr, ctx, label, bounds);
+        render_button_label (cr, ctx, label, bounds, text_scale);
     }
 }
 
@@ -122,6 +124,7 @@ eek_get_style_context_for_button (EekRenderer *self,
                                   const char *name,
                                   const char *outline_name,
                                   const char *locked_class,
+                                  const char *const *classes,
                                   uint64_t     pressed)
 {
     GtkStyleContext *ctx = self->button_context;
@@ -141,37 +144,80 @@ eek_get_style_context_for_button (EekRenderer *self,
         gtk_style_context_add_class(ctx, locked_class);
     }
     gtk_style_context_add_class(ctx, outline_name);
+    for (const char *const *class = classes; *class; class++) {
+        gtk_style_context_add_class(ctx, *class);
+    }
     return ctx;
 }
 
 /// Interface for Rust.
 void eek_put_style_context_for_button(GtkStyleContext *ctx,
                                       const char *outline_name,
-                                      const char *locked_class) {
+                                      const char *locked_class,
+                                      const char *const *classes) {
     // Save and restore functions don't work if gtk_render_* was used in between
     gtk_style_context_set_state(ctx, GTK_STATE_FLAG_NORMAL);
     gtk_style_context_remove_class(ctx, outline_name);
     if (locked_class) {
         gtk_style_context_remove_class(ctx, locked_class);
     }
+    for (const char *const *class = classes; *class; class++) {
+        gtk_style_context_remove_class(ctx, *class);
+    }
+}
+
+static void
+scale_font (PangoFontDescription *font, double factor)
+{
+    gint size = pango_font_description_get_size (font) * factor;
+    if (pango_font_description_get_size_is_absolute (font)) {
+        pango_font_description_set_absolute_size (font, size);
+    } else {
+        pango_font_description_set_size (font, size);
+    }
 }
 
 static void
 render_button_label (cairo_t     *cr,
                      GtkStyleContext *ctx,
                      const gchar *label,
-                     EekBounds bounds)
+                     EekBounds bounds,
+                     double text_scale)
 {
     PangoFontDescription *font;
-    gtk_style_context_get(ctx,
-                          gtk_style_context_get_state(ctx),
-                          "font", &font,
-                          NULL);
+    GtkStateFlags state = gtk_style_context_get_state(ctx);
+    gtk_style_context_get(ctx, state, "font", &font, NULL);
+    scale_font (font, text_scale);
+
     PangoLayout *layout = pango_cairo_create_layout (cr);
     pango_layout_set_font_description (layout, font);
+    pango_layout_set_text (layout, label, -1);
+
+    /* Shrink labels which would spill over the outline,
+       like "ABC" on small keys, or anything with large text */
+    GtkBorder margin, border, padding;
+    gtk_style_context_get_margin(ctx, state, &margin);
+    gtk_style_context_get_border(ctx, state, &border);
+    gtk_style_context_get_padding(ctx, state, &padding);
+    double available_width = bounds.width
+        - margin.left - border.left - padding.left
+        - margin.right - border.right - padding.right;
+    double available_height = bounds.height
+        - margin.top - border.top - padding.top
+        - margin.bottom - border.bottom - padding.bottom;
+
+    PangoRectangle fitting = { 0, };
+    pango_layout_get_extents (layout, NULL, &fitting);
+    double fit = MIN(
+        available_width * PANGO_SCALE / MAX(fitting.width, 1),
+        available_height * PANGO_SCALE / MAX(fitting.height, 1)
+    );
+    if (fit < 1.0 && fit > 0.0) {
+        scale_font (font, fit);
+        pango_layout_set_font_description (layout, font);
+    }
     pango_font_description_free (font);
 
-    pango_layout_set_text (layout, label, -1);
     PangoLayoutLine *line = pango_layout_get_line_readonly(layout, 0);
     if (line->resolved_dir == PANGO_DIRECTION_RTL) {
         pango_layout_set_alignment (layout, PANGO_ALIGN_RIGHT);
@@ -236,7 +282,9 @@ eek_renderer_free (EekRenderer        *self)
     g_object_unref(self->css_provider);
     g_object_unref(self->view_context);
     g_object_unref(self->button_context);
+    g_object_unref(self->hint_context);
     g_clear_signal_handler (&self->theme_name_id, gtk_settings_get_default());
+    g_clear_signal_handler (&self->prefer_dark_id, gtk_settings_get_default());
 
     // this is where renderer-specific surfaces would be released
 
@@ -269,6 +317,61 @@ static GType button_type(void) {
     return type;
 }
 
+static GType hint_type(void) {
+    static GType type = 0;
+    if (!type) {
+        type = new_type("sq_hint");
+    }
+    return type;
+}
+
+/// Draws the secondary label in the top right corner of the button.
+/// Interface for Rust.
+void eek_render_button_hint(EekRenderer *self,
+                            cairo_t *cr,
+                            GtkStyleContext *button_ctx,
+                            EekBounds bounds,
+                            const gchar *hint,
+                            double text_scale) {
+    GtkStyleContext *ctx = self->hint_context;
+    g_autoptr (GtkWidgetPath) path = NULL;
+    path = gtk_widget_path_copy (gtk_style_context_get_path (button_ctx));
+    gtk_widget_path_append_type (path, hint_type ());
+    gtk_style_context_set_path (ctx, path);
+    GtkStateFlags state = gtk_style_context_get_state (button_ctx);
+    gtk_style_context_set_state (ctx, state);
+
+    PangoFontDescription *font;
+    gtk_style_context_get (ctx, state, "font", &font, NULL);
+    scale_font (font, text_scale);
+    PangoLayout *layout = pango_cairo_create_layout (cr);
+    pango_layout_set_font_description (layout, font);
+    pango_font_description_free (font);
+    pango_layout_set_text (layout, hint, -1);
+
+    PangoRectangle extents = { 0, };
+    pango_layout_get_extents (layout, NULL, &extents);
+
+    GtkBorder margin, border, padding;
+    gtk_style_context_get_margin (button_ctx, state, &margin);
+    gtk_style_context_get_border (button_ctx, state, &border);
+    gtk_style_context_get_padding (ctx, state, &padding);
+
+    GdkRGBA color = {0};
+    gtk_style_context_get_color (ctx, state, &color);
+
+    cairo_save (cr);
+    cairo_move_to (cr,
+        bounds.width - margin.right - border.right - padding.right
+            - (double)extents.width / PANGO_SCALE,
+        margin.top + border.top + padding.top);
+    cairo_set_source_rgba (cr, color.red, color.green, color.blue, color.alpha);
+    pango_cairo_show_layout (cr, layout);
+    cairo_restore (cr);
+    g_object_unref (layout);
+    gtk_style_context_set_state (ctx, GTK_STATE_FLAG_NORMAL);
+}
+
 
 static void
 on_gtk_theme_name_changed (GtkSettings *settings, gpointer foo, EekRenderer *self)
@@ -284,6 +387,8 @@ on_gtk_theme_name_changed (GtkSettings *settings, gpointer foo, EekRenderer *sel
                                      GTK_STYLE_PROVIDER(self->css_provider));
   gtk_style_context_remove_provider (self->view_context,
                                      GTK_STYLE_PROVIDER(self->css_provider));
+  gtk_style_context_remove_provider (self->hint_context,
+                                     GTK_STYLE_PROVIDER(self->css_provider));
 
   g_set_object (&self->css_provider, squeek_load_style());
 
@@ -296,6 +401,9 @@ on_gtk_theme_name_changed (GtkSettings *settings, gpointer foo, EekRenderer *sel
   gtk_style_context_add_provider (self->view_context,
                                   GTK_STYLE_PROVIDER(self->css_provider),
                                   GTK_STYLE_PROVIDER_PRIORITY_APPLICATION);
+  gtk_style_context_add_provider (self->hint_context,
+                                  GTK_STYLE_PROVIDER(self->css_provider),
+                                  GTK_STYLE_PROVIDER_PRIORITY_APPLICATION);
 }
 
 
@@ -311,6 +419,8 @@ renderer_init (EekRenderer *self)
 
     self->theme_name_id = g_signal_connect (gtk_settings, "notify::gtk-theme-name",
                                             G_CALLBACK (on_gtk_theme_name_changed), self);
+    self->prefer_dark_id = g_signal_connect (gtk_settings, "notify::gtk-application-prefer-dark-theme",
+                                             G_CALLBACK (on_gtk_theme_name_changed), self);
 
     self->css_provider = squeek_load_style();
 }
@@ -332,9 +442,15 @@ eek_renderer_new (Layout  *keyboard,
     renderer->view_context = gtk_style_context_new();
     gtk_style_context_set_path(renderer->view_context, path);
     gtk_widget_path_unref(path);
-    if (squeek_layout_get_kind(keyboard->layout) == ARRANGEMENT_KIND_WIDE) {
+    enum squeek_arrangement_kind kind = squeek_layout_get_kind(keyboard->layout);
+    // Split is styled like wide, with its own class on top.
+    gboolean wide = kind == ARRANGEMENT_KIND_WIDE || kind == ARRANGEMENT_KIND_SPLIT;
+    if (wide) {
         gtk_style_context_add_class(renderer->view_context, "wide");
     }
+    if (kind == ARRANGEMENT_KIND_SPLIT) {
+        gtk_style_context_add_class(renderer->view_context, "split");
+    }
     gtk_style_context_add_class(renderer->view_context, (char*)&keyboard->style_name);
     gtk_style_context_add_provider (renderer->view_context,
         GTK_STYLE_PROVIDER(renderer->css_provider),
@@ -343,9 +459,12 @@ eek_renderer_new (Layout  *keyboard,
     /* Create a style context for the buttons */
     path = gtk_widget_path_new();
     gtk_widget_path_append_type(path, view_type());
-    if (squeek_layout_get_kind(keyboard->layout) == ARRANGEMENT_KIND_WIDE) {
+    if (wide) {
         gtk_widget_path_iter_add_class(path, -1, "wide");
     }
+    if (kind == ARRANGEMENT_KIND_SPLIT) {
+        gtk_widget_path_iter_add_class(path, -1, "split");
+    }
     /* Add style classes based on purpose */
     switch (squeek_layout_get_purpose (keyboard->layout)) {
     case ZWP_TEXT_INPUT_V3_CONTENT_PURPOSE_NORMAL:
@@ -404,6 +523,13 @@ eek_renderer_new (Layout  *keyboard,
     gtk_style_context_add_provider (renderer->button_context,
         GTK_STYLE_PROVIDER(renderer->css_provider),
         GTK_STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+    /* The path gets copied from the button for each hint */
+    renderer->hint_context = gtk_style_context_new ();
+    gtk_style_context_set_parent(renderer->hint_context, renderer->button_context);
+    gtk_style_context_add_provider (renderer->hint_context,
+        GTK_STYLE_PROVIDER(renderer->css_provider),
+        GTK_STYLE_PROVIDER_PRIORITY_APPLICATION);
     return renderer;
 }
 
@@ -438,13 +564,26 @@ eek_renderer_get_icon_surface (const gchar *icon_name,
                                gint scale)
 {
     GError *error = NULL;
-    cairo_surface_t *surface = gtk_icon_theme_load_surface (gtk_icon_theme_get_default (),
-                                                            icon_name,
-                                                            size,
-                                                            scale,
-                                                            NULL,
-                                                            0,
-                                                            &error);
+    cairo_surface_t *surface = NULL;
+    if (g_path_is_absolute (icon_name)) {
+        /* Image files from layouts, only the shape is used */
+        g_autoptr (GdkPixbuf) pixbuf = gdk_pixbuf_new_from_file_at_scale (icon_name,
+                                                                          size * scale,
+                                                                          size * scale,
+                                                                          TRUE,
+                                                                          &error);
+        if (pixbuf) {
+            surface = gdk_cairo_surface_create_from_pixbuf (pixbuf, scale, NULL);
+        }
+    } else {
+        surface = gtk_icon_theme_load_surface (gtk_icon_theme_get_default (),
+                                               icon_name,
+                                               size,
+                                               scale,
+                                               NULL,
+                                               0,
+                                               &error);
+    }
 
     if (surface == NULL) {
         g_warning ("can't get icon surface for %s: %s",
@@ -37,10 +37,12 @@ typedef struct EekRenderer
     GtkCssProvider *css_provider; // owned
     GtkStyleContext *view_context; // owned
     GtkStyleContext *button_context; // TODO: maybe move a copy to each button
+    GtkStyleContext *hint_context; // owned
     /// Style class for rendering the view and button CSS.
     gchar *extra_style; // owned
-    // Theme name change signal handler id
+    // Theme name and dark preference change signal handler ids
     gulong theme_name_id;
+    gulong prefer_dark_id;
 
     // Mutable state
     gint scale_factor; /* the outputs scale factor */
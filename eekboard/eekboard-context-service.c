@@ -270,6 +270,23 @@ eekboard_context_service_get_keyboard (EekboardContextService *context)
     return context->keyboard;
 }
 
+void
+eekboard_context_service_refresh_keyboard (EekboardContextService *context)
+{
+    g_object_notify (G_OBJECT(context), "keyboard");
+}
+
+// Exported to Rust
+uint8_t
+eekboard_context_service_set_view (EekboardContextService *context, const char *view)
+{
+    if (!context->keyboard || !squeek_layout_set_view(context->keyboard->layout, view)) {
+        return 0;
+    }
+    eekboard_context_service_refresh_keyboard(context);
+    return 1;
+}
+
 EekboardContextService *eekboard_context_service_new(struct squeek_state_manager *state_manager)
 {
     EekboardContextService *context = g_object_new (EEKBOARD_TYPE_CONTEXT_SERVICE, NULL);
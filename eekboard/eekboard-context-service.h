@@ -42,6 +42,10 @@ EekboardContextService *eekboard_context_service_new(struct squeek_state_manager
 void eekboard_context_service_set_submission(EekboardContextService *context, struct submission *submission);
 void          eekboard_context_service_destroy (EekboardContextService *context);
 Layout *eekboard_context_service_get_keyboard(EekboardContextService *context);
+/// Call after the current keyboard changed internally
+void eekboard_context_service_refresh_keyboard(EekboardContextService *context);
+/// Returns 0 if the current keyboard has no such view
+uint8_t eekboard_context_service_set_view(EekboardContextService *context, const char *view);
 
 void eekboard_context_service_set_keymap(EekboardContextService *context,
                                          const Layout *keyboard);
@@ -0,0 +1,30 @@
+extern crate rs;
+
+use rs::imservice::ContentPurpose;
+use rs::simulator::{ Simulator, Tap };
+use std::env;
+use std::process;
+
+/// Usage: simulate LAYOUT TAP...
+/// where TAP is a button name or @x,y
+fn main() -> () {
+    let name = env::args().nth(1).expect("No layout given");
+    let taps: Vec<Tap> = env::args().skip(2)
+        .map(|arg| arg.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1)
+        }))
+        .collect();
+
+    let mut simulator = Simulator::new(&name, false, ContentPurpose::Normal);
+    for tap in taps {
+        match simulator.tap(&tap) {
+            Ok(step) => println!("{}: {}", tap, step),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            },
+        }
+    }
+    println!("Total: {:?}", simulator.get_output());
+}
@@ -49,6 +49,16 @@ pub enum Action {
     /// Erase a position behind the cursor
     Erase,
     ShowPreferences,
+    /// Dismiss the panel
+    Hide,
+    /// Switch to the next layout. Long press shows preferences.
+    NextLayout,
+    /// Switch to the previous layout. Long press shows preferences.
+    PreviousLayout,
+    /// Switch between the full width and the one-handed keyboard
+    ToggleOneHanded,
+    /// Move the one-handed keyboard to the other edge
+    FlipOneHanded,
 }
 
 impl Action {
@@ -75,4 +85,25 @@ impl Action {
             _ => false,
         }
     }
+    /// A single letter, which can be a part of a glided word
+    pub fn get_letter(&self) -> Option<char> {
+        match self {
+            Action::Submit { text: Some(text), keys: _ } => {
+                let text = text.to_str().ok()?;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(letter), None) if letter.is_alphabetic() => Some(letter),
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+    /// The space bar doubles as a trackpad
+    pub fn is_space(&self) -> bool {
+        match self {
+            Action::Submit { text: Some(text), keys: _ } => text.as_bytes() == b" ",
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,131 @@
+/*
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/*! Follows the light/dark preference from the settings portal. */
+
+use crate::logging;
+use crate::style;
+use crate::style::ColorScheme;
+use glib::MainContext;
+use std::env;
+use std::thread;
+use zbus::{Connection, dbus_proxy};
+use zvariant::{ OwnedValue, Value };
+
+use super::Void;
+
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+pub trait Manager {
+    fn read(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+    #[dbus_proxy(signal)]
+    fn setting_changed(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: OwnedValue,
+    ) -> fdo::Result<()>;
+}
+
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+/// Replaces the portal with another service implementing the same interface,
+/// for testing without a desktop session.
+const SERVICE_VAR: &str = "SQUEEKBOARD_SETTINGS_PORTAL";
+
+/// Older portals wrap the value returned by Read in another variant.
+fn as_u32(value: &Value) -> Option<u32> {
+    match value {
+        Value::U32(v) => Some(*v),
+        Value::Value(inner) => as_u32(inner),
+        _ => None,
+    }
+}
+
+/// Styles live on the UI thread
+fn apply(scheme: ColorScheme) {
+    MainContext::default().invoke(move || style::set_color_scheme(scheme));
+}
+
+/// Tracks the color scheme preference
+pub fn init() {
+    thread::spawn(move || {
+        if let Err(e) = start() {
+            log_print!(
+                logging::Level::Surprise,
+                "Could not track the color scheme, giving up: {:?}",
+                e,
+            );
+        }
+    });
+}
+
+fn start() -> Result<Void, zbus::Error> {
+    let conn = Connection::new_session()?;
+    let service = env::var(SERVICE_VAR)
+        .unwrap_or_else(|_| "org.freedesktop.portal.Desktop".into());
+    let manager = ManagerProxy::new_for(
+        &conn,
+        &service,
+        "/org/freedesktop/portal/desktop",
+    )?;
+
+    match manager.read(NAMESPACE, KEY) {
+        Ok(value) => match as_u32(&value) {
+            Some(v) => apply(ColorScheme::from_portal(v)),
+            None => log_print!(
+                logging::Level::Surprise,
+                "Unexpected color scheme value {:?}", value,
+            ),
+        },
+        // The portal might come later, or support the preference later.
+        Err(e) => log_print!(
+            logging::Level::Surprise,
+            "Can't read the color scheme: {:?}", e,
+        ),
+    }
+
+    manager.connect_setting_changed(|namespace, key, value| {
+        if namespace == NAMESPACE && key == KEY {
+            match as_u32(&value) {
+                Some(v) => apply(ColorScheme::from_portal(v)),
+                None => log_print!(
+                    logging::Level::Surprise,
+                    "Unexpected color scheme value {:?}", value,
+                ),
+            }
+        }
+        Ok(())
+    })?;
+
+    loop {
+        match manager.next_signal() {
+            Ok(None) => {}
+            other => log_print!(
+                logging::Level::Bug,
+                "Encountered unhandled event: {:?}",
+                other,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested_value() {
+        let value = Value::Value(Box::new(Value::U32(1)));
+        assert_eq!(as_u32(&value), Some(1));
+        assert_eq!(ColorScheme::from_portal(as_u32(&value).unwrap()), ColorScheme::PreferDark);
+        assert_eq!(as_u32(&Value::Str("dark".into())), None);
+    }
+}
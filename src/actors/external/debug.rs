@@ -3,23 +3,57 @@
  *
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
+use crate::animation;
+use crate::event_loop::ActorState;
+use crate::imservice::{ ContentHint, ContentPurpose };
 use crate::main;
+use crate::outputs;
+use crate::outputs::{ Millimeter, OutputState };
+use crate::recording;
 use crate::state;
 
+use serde::Deserialize;
+use serde_json::{ json, Value };
+use std::path::Path;
+use std::sync::mpsc;
 use std::thread;
+use std::time::{ Duration, Instant };
 use zbus::{Connection, ObjectServer, dbus_interface, fdo};
+use zvariant_derive::Type;
 
 use super::Void;
 
+use std::convert::TryFrom;
 use std::convert::TryInto;
 
 
+/// Properties of a faked output, as sent over D-Bus
+#[derive(Deserialize, Type)]
+struct FakeOutput {
+    width: i32,
+    height: i32,
+    /// Millimeters, 0 means unknown
+    phys_width: i32,
+    /// Millimeters, 0 means unknown
+    phys_height: i32,
+    scale: i32,
+    /// Takes wl_output values
+    transform: u32,
+}
+
 /// Accepts commands controlling the debug mode
 struct Manager {
     sender: main::EventLoop,
     enabled: bool,
 }
 
+impl Manager {
+    fn send(&self, event: state::Event) -> fdo::Result<()> {
+        self.sender.send(event)
+            .map_err(|e| fdo::Error::Failed(format!("Can't send to state: {}", e)))
+    }
+}
+
 #[dbus_interface(name = "sm.puri.SqueekDebug")]
 impl Manager {
     #[dbus_interface(property, name = "Enabled")]
@@ -36,6 +70,93 @@ impl Manager {
             ))
             .unwrap();
     }
+
+    /// Returns the application state and its outcome, as JSON
+    fn get_state(&self) -> fdo::Result<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(state::Event::Debug(Event::Dump(sender)))?;
+        receiver.recv_timeout(Duration::from_secs(1))
+            .map_err(|e| fdo::Error::Failed(format!("No reply from state: {}", e)))
+    }
+
+    /// Pretends that a text input got activated or deactivated.
+    /// Purpose and hint take text-input-unstable-v3 values.
+    fn set_input_method(
+        &self,
+        active: bool,
+        purpose: u32,
+        hint: u32,
+    ) -> fdo::Result<()> {
+        let im = match active {
+            true => state::InputMethod::Active(state::InputMethodDetails {
+                purpose: ContentPurpose::try_from(purpose)
+                    .map_err(|_| fdo::Error::InvalidArgs(
+                        format!("Unknown purpose {}", purpose)
+                    ))?,
+                hint: ContentHint::from_bits(hint)
+                    .ok_or_else(|| fdo::Error::InvalidArgs(
+                        format!("Unknown hint bits {:x}", hint)
+                    ))?,
+            }),
+            false => state::InputMethod::InactiveSince(Instant::now()),
+        };
+        self.send(state::Event::InputMethod(im))
+    }
+
+    /// Pretends that the output with the given name changed its properties,
+    /// or appeared if there's no such output.
+    fn set_output(&self, name: &str, output: FakeOutput) -> fdo::Result<()> {
+        let FakeOutput {
+            width,
+            height,
+            phys_width,
+            phys_height,
+            scale,
+            transform,
+        } = output;
+        let transform = outputs::c::Transform::from_u32(transform)
+            .ok_or_else(|| fdo::Error::InvalidArgs(
+                format!("Unknown transform {}", transform)
+            ))?;
+        let maybe_mm = |value| match value {
+            0 => None,
+            v => Some(Millimeter(v)),
+        };
+        let output = OutputState {
+            current_mode: Some(outputs::Mode { width, height }),
+            geometry: Some(outputs::Geometry {
+                transform,
+                phys_size: outputs::Size {
+                    width: maybe_mm(phys_width),
+                    height: maybe_mm(phys_height),
+                },
+            }),
+            scale,
+            name: Some(name.into()),
+        };
+        self.send(state::Event::Debug(Event::FakeOutput {
+            name: name.into(),
+            state: Some(output),
+        }))
+    }
+
+    /// Replays a recording made with SQUEEKBOARD_RECORD,
+    /// and returns how the outcome compares to the recorded one.
+    /// The replay doesn't send anything to applications.
+    fn replay(&self, path: &str) -> fdo::Result<String> {
+        recording::read_file(Path::new(path))
+            .and_then(|entries| recording::replay(&entries))
+            .map(|report| report.to_string())
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Pretends that the output with the given name disappeared.
+    fn remove_output(&self, name: &str) -> fdo::Result<()> {
+        self.send(state::Event::Debug(Event::FakeOutput {
+            name: name.into(),
+            state: None,
+        }))
+    }
 }
 
 fn start(mgr: Manager) -> Result<Void, Box<dyn std::error::Error>> {
@@ -65,8 +186,127 @@ pub fn init(sender: main::EventLoop) {
     });
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Event {
     Enable,
     Disable,
+    /// Asks for the state description to be sent back as JSON
+    Dump(mpsc::Sender<String>),
+    /// Changes, creates or removes the output with this name,
+    /// as if the compositor did it.
+    /// The panel is never shown on created outputs,
+    /// because the compositor doesn't know them.
+    FakeOutput {
+        name: String,
+        state: Option<OutputState>,
+    },
+}
+
+fn describe_im(im: &state::InputMethod, now: Instant) -> Value {
+    match im {
+        state::InputMethod::Active(details) => json!({
+            "active": true,
+            "purpose": format!("{:?}", details.purpose),
+            "hint": format!("{:?}", details.hint),
+        }),
+        state::InputMethod::InactiveSince(since) => json!({
+            "active": false,
+            "inactive_for_ms": now.saturating_duration_since(*since).as_millis() as u64,
+        }),
+    }
+}
+
+fn describe_output(output: &OutputState) -> Value {
+    json!({
+        "name": output.name,
+        "scale": output.scale,
+        "mode": output.current_mode.map(|mode| json!({
+            "width": mode.width,
+            "height": mode.height,
+        })),
+        "geometry": output.geometry.map(|geometry| json!({
+            "transform": format!("{:?}", geometry.transform),
+            "width_mm": geometry.phys_size.width.map(|mm| mm.0),
+            "height_mm": geometry.phys_size.height.map(|mm| mm.0),
+        })),
+    })
+}
+
+fn describe_outcome(outcome: &state::Outcome, now: Instant) -> Value {
+    json!({
+        "panel": match &outcome.panel {
+            animation::Outcome::Visible { output, height, offset, placement, contents } => json!({
+                "visible": true,
+                "output": format!("{:?}", output),
+                "height": {
+                    "pixels": height.pixels,
+                    "scale_factor": height.scale_factor.as_f64(),
+                },
+                "offset": offset.pixels,
+                "placement": format!("{:?}", placement),
+                "layout": contents.name,
+                "overlay": contents.overlay_name,
+                "arrangement": format!("{:?}", contents.kind),
+                "purpose": format!("{:?}", contents.purpose),
+            }),
+            animation::Outcome::Hidden => json!({ "visible": false }),
+        },
+        "im": describe_im(&outcome.im, now),
+    })
+}
+
+/// A description of the state meant for debugging.
+/// It's not a stable interface.
+pub fn dump(app: &state::Application, now: Instant) -> String {
+    let outputs: Vec<Value> = app.outputs.iter()
+        .map(|(id, output)| {
+            let mut description = describe_output(output);
+            description["id"] = json!(format!("{:?}", id));
+            description
+        })
+        .collect();
+    let description = json!({
+        "state": {
+            "im": describe_im(&app.im, now),
+            "visibility_override": format!("{:?}", app.visibility_override),
+            "physical_keyboard": format!("{:?}", app.physical_keyboard),
+            "keyboard_policy": format!("{:?}", app.keyboard_policy),
+            "screen_keyboard_enabled": app.screen_keyboard_enabled,
+            "debug_mode_enabled": app.debug_mode_enabled,
+            "output_policy": format!("{:?}", app.output_policy),
+            "focused_output_name": app.focused_output_name,
+            "preferred_output": app.preferred_output.map(|id| format!("{:?}", id)),
+            "outputs": outputs,
+            "layout_choice": {
+                "name": app.layout_choice.name,
+                "source": format!("{:?}", app.layout_choice.source),
+            },
+            "overlay_layout": app.overlay_layout.as_ref()
+                .map(|layout| format!("{:?}", layout)),
+            "panel_height": format!("{:?}", app.panel_height),
+            "floating": format!("{:?}", app.floating),
+            "slide_duration_ms": app.slide_duration.as_millis() as u64,
+            "panel_allocation": app.panel_allocation
+                .map(|(output, size)| format!("{:?} on {:?}", size, output)),
+            "panel_scale": app.panel_scale
+                .map(|(output, scale)| format!("{} on {:?}", scale.as_f64(), output)),
+        },
+        "outcome": describe_outcome(&app.get_outcome(now), now),
+    });
+    serde_json::to_string_pretty(&description)
+        .unwrap_or_else(|e| format!("{{\"error\": \"{}\"}}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dump_is_json() {
+        let now = Instant::now();
+        let app = state::Application::new(now);
+        let dumped: Value = serde_json::from_str(&dump(&app, now)).unwrap();
+        assert_eq!(dumped["state"]["layout_choice"]["name"], json!("us"));
+        assert_eq!(dumped["outcome"]["panel"]["visible"], json!(false));
+    }
 }
@@ -0,0 +1,186 @@
+/*
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/*! Detects physical keyboards attached to the device.
+ *
+ * The Wayland seat can't tell, because its keyboard capability
+ * also covers virtual keyboards, including squeekboard's own.
+ * Instead, input devices are listed by the kernel,
+ * and rescanned whenever something appears or disappears in /dev/input.
+ */
+
+use crate::logging;
+use crate::main;
+use crate::state;
+use crate::state::Presence;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+
+// Traits
+use gio::prelude::{ FileExt, FileMonitorExt };
+use crate::logging::Warn;
+
+
+/// Provides the list of input devices,
+/// in the format of /proc/bus/input/devices
+pub trait Source {
+    fn read_devices(&self) -> io::Result<String>;
+}
+
+/// The real list, as seen by the kernel
+pub struct Kernel;
+
+impl Source for Kernel {
+    fn read_devices(&self) -> io::Result<String> {
+        fs::read_to_string("/proc/bus/input/devices")
+    }
+}
+
+/// Key codes from KEY_Q to KEY_P, the top letter row.
+/// They all fit in the lowest word of the bitmap,
+/// whether the kernel prints it in 32-bit or 64-bit words.
+const LETTER_ROW: std::ops::RangeInclusive<u32> = 16..=25;
+
+/// Power buttons, volume keys and the like also count as keyboards
+/// for the kernel, so only devices with letters are taken.
+fn has_letters(key_bitmap: &str) -> bool {
+    key_bitmap.split_whitespace()
+        .last()
+        .and_then(|word| u64::from_str_radix(word, 16).ok())
+        .map(|word| LETTER_ROW.clone().all(|code| word & (1 << code) != 0))
+        .unwrap_or(false)
+}
+
+/// Checks the device list for anything that can type text.
+pub fn find_keyboard(devices: &str) -> Presence {
+    let found = devices.lines()
+        .filter_map(|line| line.strip_prefix("B: KEY="))
+        .any(has_letters);
+    if found { Presence::Present }
+    else { Presence::Missing }
+}
+
+/// An unreadable list is treated like no keyboard,
+/// so that the panel doesn't disappear for good.
+pub fn detect(source: &dyn Source) -> Presence {
+    source.read_devices()
+        .or_print(logging::Problem::Warning, "Can't list input devices")
+        .map(|devices| find_keyboard(&devices))
+        .unwrap_or(Presence::Missing)
+}
+
+thread_local! {
+    /// Stops watching when dropped, so it's kept for the program lifetime.
+    static MONITOR: RefCell<Option<gio::FileMonitor>> = RefCell::new(None);
+}
+
+fn send(sender: &main::EventLoop, presence: Presence) {
+    sender.send(state::Event::PhysicalKeyboard(presence))
+        .or_print(logging::Problem::Warning, "Can't send to state manager");
+}
+
+/// Sends the current presence, and then every change.
+/// Must be called on the UI thread.
+pub fn init(sender: main::EventLoop) {
+    send(&sender, detect(&Kernel));
+
+    #[cfg(feature = "glib_v0_14")]
+    let dir = gio::File::for_path("/dev/input");
+    #[cfg(not(feature = "glib_v0_14"))]
+    let dir = gio::File::new_for_path("/dev/input");
+
+    let monitor = dir.monitor_directory(
+        gio::FileMonitorFlags::NONE,
+        None::<&gio::Cancellable>,
+    )
+        .or_print(
+            logging::Problem::Warning,
+            "Can't watch input devices, keyboards plugged in later will be missed",
+        );
+
+    if let Some(monitor) = monitor {
+        monitor.connect_changed(move |_monitor, _file, _other, event| {
+            match event {
+                gio::FileMonitorEvent::Created
+                | gio::FileMonitorEvent::Deleted
+                    => send(&sender, detect(&Kernel)),
+                _ => {},
+            }
+        });
+        MONITOR.with(|m| *m.borrow_mut() = Some(monitor));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LAPTOP: &str = r#"I: Bus=0019 Vendor=0000 Product=0001 Version=0000
+N: Name="Power Button"
+P: Phys=PNP0C0C/button/input0
+S: Sysfs=/devices/LNXSYSTM:00/LNXSYBUS:00/PNP0C0C:00/input/input0
+U: Uniq=
+H: Handlers=kbd event0
+B: PROP=0
+B: EV=3
+B: KEY=10000000000000 0
+
+I: Bus=0011 Vendor=0001 Product=0001 Version=ab41
+N: Name="AT Translated Set 2 keyboard"
+P: Phys=isa0060/serio0/input0
+S: Sysfs=/devices/platform/i8042/serio0/input/input3
+U: Uniq=
+H: Handlers=sysrq kbd event3 leds
+B: PROP=0
+B: EV=120013
+B: KEY=402000000 3803078f800d001 feffffdfffefffff fffffffffffffffe
+B: MSC=10
+B: LED=7
+"#;
+
+    const PHONE: &str = r#"I: Bus=0019 Vendor=0001 Product=0001 Version=0100
+N: Name="1c21800.lradc"
+P: Phys=sun4i_lradc/input0
+S: Sysfs=/devices/platform/soc/1c21800.lradc/input/input0
+U: Uniq=
+H: Handlers=kbd event0
+B: PROP=0
+B: EV=3
+B: KEY=c000000000000 0
+"#;
+
+    struct Fixed(Option<&'static str>);
+
+    impl Source for Fixed {
+        fn read_devices(&self) -> io::Result<String> {
+            self.0.map(String::from)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "gone"))
+        }
+    }
+
+    #[test]
+    fn keyboard_found() {
+        assert_matches!(detect(&Fixed(Some(LAPTOP))), Presence::Present);
+    }
+
+    #[test]
+    fn buttons_are_not_keyboards() {
+        assert_matches!(detect(&Fixed(Some(PHONE))), Presence::Missing);
+    }
+
+    #[test]
+    fn unreadable_is_missing() {
+        assert_matches!(detect(&Fixed(None)), Presence::Missing);
+    }
+
+    /// 32-bit kernels print the bitmap in shorter words.
+    #[test]
+    fn short_words() {
+        assert_matches!(
+            find_keyboard("B: KEY=1000 0 ffefffff fffffffe\n"),
+            Presence::Present
+        );
+    }
+}
@@ -6,7 +6,10 @@
 
 /*! Contains actors with custom event loops, not based off of the event_loop module. */
  
+#[cfg(feature = "zbus_v1_5")]
+pub mod color_scheme;
 pub mod debug;
+pub mod keyboards;
 #[cfg(feature = "zbus_v1_5")]
 pub mod screensaver;
 
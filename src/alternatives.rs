@@ -0,0 +1,96 @@
+/*
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/*! Offers other words after gesture typing */
+
+use gtk;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::rc::Rc;
+use crate::layout::c::{ Bounds, EekGtkKeyboard };
+use crate::logging;
+use crate::submission::Submission;
+
+// Traits
+use glib::translate::FromGlibPtrNone;
+use gtk::prelude::*;
+
+
+thread_local! {
+    static ALTERNATIVES: RefCell<Option<gtk::Popover>> = RefCell::new(None);
+}
+
+/// Shows the words above the position, given in widget coordinates.
+/// Choosing one replaces the `typed` text.
+pub fn show(
+    window: EekGtkKeyboard,
+    position: Bounds,
+    submission: Rc<RefCell<Submission>>,
+    typed: CString,
+    words: Vec<CString>,
+) {
+    hide();
+    if words.is_empty() {
+        return;
+    }
+    unsafe { gtk::set_initialized() };
+    let window = unsafe { gtk::Widget::from_glib_none(window.0) };
+
+    let popover = gtk::Popover::new(Some(&window));
+    // Typing goes on while the words are offered.
+    popover.set_modal(false);
+    popover.set_can_focus(false);
+    popover.set_position(gtk::PositionType::Top);
+    popover.set_constrain_to(gtk::PopoverConstraint::None);
+    #[cfg(feature = "glib_v0_14")]
+    popover.style_context().add_class("sq_alternatives");
+    #[cfg(not(feature = "glib_v0_14"))]
+    popover.get_style_context().add_class("sq_alternatives");
+
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    for word in words {
+        let label = word.to_string_lossy();
+        #[cfg(feature = "glib_v0_14")]
+        let button = gtk::Button::with_label(label.trim_end());
+        #[cfg(not(feature = "glib_v0_14"))]
+        let button = gtk::Button::new_with_label(label.trim_end());
+        button.set_relief(gtk::ReliefStyle::None);
+        button.set_can_focus(false);
+
+        let submission = submission.clone();
+        let typed = typed.clone();
+        let popover = popover.clone();
+        button.connect_clicked(move |_button| {
+            let result = submission.borrow_mut().replace_text(&typed, &word);
+            if let Err(e) = result {
+                log_print!(
+                    logging::Level::Warning,
+                    "Can't replace the glided word: {:?}", e,
+                );
+            }
+            popover.popdown();
+        });
+        row.add(&button);
+    }
+    row.show_all();
+    popover.add(&row);
+
+    popover.set_pointing_to(&gtk::Rectangle {
+        x: position.x.ceil() as i32,
+        y: position.y.ceil() as i32,
+        width: position.width.floor() as i32,
+        height: position.height.floor() as i32,
+    });
+    popover.popup();
+    ALTERNATIVES.with(|alternatives| *alternatives.borrow_mut() = Some(popover));
+}
+
+/// The words are only useful until typing goes on
+pub fn hide() {
+    ALTERNATIVES.with(|alternatives| {
+        if let Some(popover) = alternatives.borrow_mut().take() {
+            unsafe { popover.destroy() };
+        }
+    });
+}
@@ -4,16 +4,39 @@
 
 /*! Animation details */
 
-use std::time::Duration;
+use std::time::{ Duration, Instant };
 
 use crate::imservice::ContentPurpose;
 use crate::layout::ArrangementKind;
 use crate::outputs::OutputId;
+use crate::panel;
 use crate::panel::PixelSize;
 
 /// The keyboard should hide after this has elapsed to prevent flickering.
 pub const HIDING_TIMEOUT: Duration = Duration::from_millis(200);
 
+/// Time between animation steps. Roughly one frame at 60Hz.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The last change of the panel between shown and hidden
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Transition {
+    pub visible: bool,
+    pub since: Instant,
+}
+
+impl Transition {
+    /// How far along the slide is, from 0 to 1
+    pub fn get_progress(&self, now: Instant, duration: Duration) -> f64 {
+        if duration.as_millis() == 0 {
+            1.0
+        } else {
+            let elapsed = now.saturating_duration_since(self.since);
+            (elapsed.as_secs_f64() / duration.as_secs_f64()).min(1.0)
+        }
+    }
+}
+
 /// Description of parameters which influence panel contents
 #[derive(PartialEq, Clone, Debug)]
 pub struct Contents {
@@ -23,12 +46,27 @@ pub struct Contents {
     pub purpose: ContentPurpose,
 }
 
+/// Where the panel goes on the output
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Placement {
+    /// Along the bottom edge, reserving space
+    Docked,
+    /// Above applications
+    Floating {
+        width: PixelSize,
+        position: panel::Position,
+    },
+}
+
 /// The outwardly visible state of visibility
 #[derive(PartialEq, Debug, Clone)]
 pub enum Outcome {
     Visible {
         output: OutputId,
         height: PixelSize,
+        /// How far the panel is slid away, towards the bottom edge
+        offset: PixelSize,
+        placement: Placement,
         contents: Contents,
     },
     Hidden,
@@ -45,18 +45,21 @@ impl fmt::Display for DataSource {
 
 /// Returns ordered names treating `name` as the base name,
 /// ignoring any `+` inside.
+/// Split falls back to wide layouts, split at their markers.
 fn _get_arrangement_names(name: &str, arrangement: ArrangementKind)
     -> Vec<(ArrangementKind, String)>
 {
-    let name_with_arrangement = match arrangement {    
-        ArrangementKind::Base => name.into(),
-        ArrangementKind::Wide => format!("{}_wide", name),
-    };
-    
     let mut ret = Vec::new();
-    if name_with_arrangement != name {
-        ret.push((arrangement, name_with_arrangement));
-    }
+    match arrangement {
+        ArrangementKind::Base => {},
+        ArrangementKind::Wide => {
+            ret.push((arrangement, format!("{}_wide", name)));
+        },
+        ArrangementKind::Split => {
+            ret.push((arrangement, format!("{}_split", name)));
+            ret.push((arrangement, format!("{}_wide", name)));
+        },
+    };
     ret.push((ArrangementKind::Base, name.into()));
     ret
 }
@@ -190,7 +193,7 @@ fn iter_layout_sources(
     to_layout_sources(paths, layout_storage)
 }
 
-fn load_layout_data(source: DataSource)
+fn load_layout_data(kind: ArrangementKind, source: DataSource)
     -> Result<crate::layout::LayoutParseData, LoadError>
 {
     let handler = logging::Print {};
@@ -199,13 +202,15 @@ fn load_layout_data(source: DataSource)
             parsing::Layout::from_file(path.clone())
                 .map_err(LoadError::BadData)
                 .and_then(|layout|
-                    layout.build(handler).0.map_err(LoadError::BadKeyMap)
+                    layout.build_arranged(handler, kind).0
+                        .map_err(LoadError::BadKeyMap)
                 )
         },
         DataSource::Resource(name) => {
             parsing::Layout::from_resource(&name)
                 .and_then(|layout|
-                    layout.build(handler).0.map_err(LoadError::BadKeyMap)
+                    layout.build_arranged(handler, kind).0
+                        .map_err(LoadError::BadKeyMap)
                 )
         },
     }
@@ -224,7 +229,7 @@ fn load_layout_data_with_fallback(
         .or_else(|| Some(PathBuf::from("/usr/share/misc/squeekboard/keyboards")));
 
     for (kind, source) in iter_layout_sources(&name, kind, purpose, overlay, path) {
-        let layout = load_layout_data(source.clone());
+        let layout = load_layout_data(kind, source.clone());
         match layout {
             Err(e) => match (e, source) {
                 (
@@ -355,6 +360,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preferences_order_split() {
+        let sources = iter_layout_sources("nb", ArrangementKind::Split, ContentPurpose::Normal, None, None);
+
+        assert_eq!(
+            sources.collect::<Vec<_>>(),
+            vec!(
+                (ArrangementKind::Split, DataSource::Resource("nb_split".into())),
+                (ArrangementKind::Split, DataSource::Resource("nb_wide".into())),
+                (ArrangementKind::Base, DataSource::Resource("nb".into())),
+                (
+                    ArrangementKind::Split,
+                    DataSource::Resource("us_split".into())
+                ),
+                (
+                    ArrangementKind::Split,
+                    DataSource::Resource("us_wide".into())
+                ),
+                (
+                    ArrangementKind::Base,
+                    DataSource::Resource("us".into())
+                ),
+            )
+        );
+    }
+
     #[test]
     fn test_preferences_order_overlay() {
         let sources = iter_layout_sources("nb", ArrangementKind::Base, ContentPurpose::Normal, Some("terminal"), None);
@@ -7,7 +7,7 @@
 use std::collections::{ HashMap, HashSet };
 use std::ffi::CString;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 use std::vec::Vec;
 
 use xkbcommon::xkb;
@@ -21,6 +21,7 @@ use crate::keyboard::{
 use crate::layout;
 use crate::logging;
 use crate::resources;
+use crate::xdg;
 
 // traits, derives
 use serde::Deserialize;
@@ -36,10 +37,16 @@ use crate::logging::Warn;
 pub struct Layout {
     #[serde(default)]
     margins: Margins,
+    /// Width of the gap left at split markers in the split arrangement.
+    /// Twice the width of the default outline if missing.
+    split_gap: Option<f64>,
     views: HashMap<String, Vec<ButtonIds>>,
     #[serde(default)] 
     buttons: HashMap<String, ButtonMeta>,
-    outlines: HashMap<String, Outline>
+    outlines: HashMap<String, Outline>,
+    /// Where the layout was loaded from, for finding icon files
+    #[serde(skip)]
+    source_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Default)]
@@ -53,6 +60,15 @@ struct Margins {
 /// Buttons are embedded in a single string
 type ButtonIds = String;
 
+/// Where rows get split in the split arrangement.
+/// Ignored in other arrangements.
+const SPLIT_MARKER: &str = "||";
+
+fn split_row(row: &str) -> impl Iterator<Item=&str> {
+    row.split_ascii_whitespace()
+        .filter(|name| *name != SPLIT_MARKER)
+}
+
 /// All info about a single button
 /// Buttons can have multiple instances though.
 #[derive(Debug, Default, Deserialize, PartialEq)]
@@ -74,10 +90,18 @@ struct ButtonMeta {
     modifier: Option<Modifier>,
     /// If not present, will be derived from text or the button ID
     label: Option<String>,
-    /// Conflicts with label
+    /// Conflicts with label.
+    /// Either the name of an icon from the theme,
+    /// or the path to an image file, relative to the layout file
+    /// or to the user's icon directory.
     icon: Option<String>,
+    /// Shown small in the corner, next to the label or icon
+    hint_label: Option<String>,
     /// The name of the outline. If not present, will be "default"
     outline: Option<String>,
+    /// Extra CSS classes, for styling groups of buttons
+    #[serde(default)]
+    classes: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
@@ -98,6 +122,17 @@ enum Action {
     /// Remove last character
     #[serde(rename="erase")]
     Erase,
+    /// Hide the keyboard
+    #[serde(rename="hide")]
+    Hide,
+    #[serde(rename="next_layout")]
+    NextLayout,
+    #[serde(rename="previous_layout")]
+    PreviousLayout,
+    #[serde(rename="one_handed")]
+    OneHanded,
+    #[serde(rename="one_handed_flip")]
+    OneHandedFlip,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
@@ -149,16 +184,32 @@ impl Layout {
                 .read(true)
                 .open(&path)?
         );
-        serde_yaml::from_reader(infile).map_err(Error::Yaml)
+        serde_yaml::from_reader::<_, Layout>(infile)
+            .map(|layout| Layout {
+                source_dir: path.parent().map(PathBuf::from),
+                ..layout
+            })
+            .map_err(Error::Yaml)
     }
 
-    pub fn build<H: logging::Handler>(self, mut warning_handler: H)
+    pub fn build<H: logging::Handler>(self, warning_handler: H)
+        -> (Result<crate::layout::LayoutParseData, FormattingError>, H)
+    {
+        self.build_arranged(warning_handler, layout::ArrangementKind::Base)
+    }
+
+    /// Like `build`, but the split arrangement leaves gaps at split markers.
+    pub fn build_arranged<H: logging::Handler>(
+        self,
+        mut warning_handler: H,
+        kind: layout::ArrangementKind,
+    )
         -> (Result<crate::layout::LayoutParseData, FormattingError>, H)
     {
         let button_names = self.views.values()
             .flat_map(|rows| {
                 rows.iter()
-                    .flat_map(|row| row.split_ascii_whitespace())
+                    .flat_map(|row| split_row(row))
             });
         
         let button_names: HashSet<&str>
@@ -175,9 +226,23 @@ impl Layout {
                 )
             )}).collect();
 
-        let symbolmap: HashMap<String, KeyCode> = generate_keycodes(
-            extract_symbol_names(&button_actions)
-        );
+        // The space bar moves the cursor with those,
+        // so every layout has them.
+        let symbol_names: HashSet<String> = extract_symbol_names(&button_actions)
+            .chain(CURSOR_KEYSYMS.iter().map(|name| String::from(*name)))
+            .collect();
+
+        let symbolmap: HashMap<String, KeyCode> = generate_keycodes(symbol_names);
+
+        let get_cursor_key = |name: &str| symbolmap.get(name)
+            .expect(&format!("{} missing from symbol map", name))
+            .clone();
+        let cursor_keys = layout::CursorKeys {
+            left: get_cursor_key("Left"),
+            right: get_cursor_key("Right"),
+            up: get_cursor_key("Up"),
+            down: get_cursor_key("Down"),
+        };
 
         let button_states = HashMap::<String, Key>::from_iter(
             button_actions.into_iter().map(|(name, action)| {
@@ -219,27 +284,46 @@ impl Layout {
 
         let button_states_cache = button_states;
 
+        let icon_dirs: Vec<PathBuf> = self.source_dir.iter()
+            .cloned()
+            .chain(xdg::data_path("squeekboard/icons"))
+            .collect();
+
+        let split_gap = match kind {
+            layout::ArrangementKind::Split => self.split_gap
+                .or_else(|| {
+                    self.outlines.get("default")
+                        .map(|outline| outline.width * 2.0)
+                })
+                .unwrap_or(0.0),
+            _ => 0.0,
+        };
+
         let views: Vec<_> = self.views.iter()
             .map(|(name, view)| {
                 let rows = view.iter().map(|row| {
-                    let buttons = row.split_ascii_whitespace()
-                        .map(|name| {
-                            create_button(
-                                &self.buttons,
-                                &self.outlines,
-                                name,
-                                button_states_cache.get(name.into())
-                                    .expect("Button state not created")
-                                    .clone(),
-                                &mut warning_handler,
-                            )
-                        });
-                    layout::Row::new(
-                        add_offsets(
-                            buttons,
-                            |button| button.size.width,
-                        ).collect()
-                    )
+                    let mut offset = 0.0;
+                    let mut buttons = Vec::new();
+                    for name in row.split_ascii_whitespace() {
+                        if name == SPLIT_MARKER {
+                            offset += split_gap;
+                            continue;
+                        }
+                        let button = create_button(
+                            &self.buttons,
+                            &self.outlines,
+                            &icon_dirs,
+                            name,
+                            button_states_cache.get(name.into())
+                                .expect("Button state not created")
+                                .clone(),
+                            &mut warning_handler,
+                        );
+                        let width = button.size.width;
+                        buttons.push((offset, button));
+                        offset += width;
+                    }
+                    layout::Row::new(buttons)
                 });
                 let rows = add_offsets(rows, |row| row.get_size().height)
                     .collect();
@@ -274,6 +358,7 @@ impl Layout {
                     CString::new(keymap_str)
                         .expect("Invalid keymap string generated")
                 ).collect(),
+                cursor_keys,
                 // FIXME: use a dedicated field
                 margins: layout::Margins {
                     top: self.margins.top,
@@ -386,6 +471,14 @@ fn create_action<H: logging::Handler>(
             Action::ShowPrefs
         ) => crate::action::Action::ShowPreferences,
         SubmitData::Action(Action::Erase) => action::Action::Erase,
+        SubmitData::Action(Action::Hide) => action::Action::Hide,
+        SubmitData::Action(Action::NextLayout) => action::Action::NextLayout,
+        SubmitData::Action(Action::PreviousLayout)
+            => action::Action::PreviousLayout,
+        SubmitData::Action(Action::OneHanded)
+            => action::Action::ToggleOneHanded,
+        SubmitData::Action(Action::OneHandedFlip)
+            => action::Action::FlipOneHanded,
         SubmitData::Keysym(keysym) => crate::action::Action::Submit {
             text: None,
             keys: vec!(crate::action::KeySym(
@@ -449,6 +542,7 @@ fn create_action<H: logging::Handler>(
 fn create_button<H: logging::Handler>(
     button_info: &HashMap<String, ButtonMeta>,
     outlines: &HashMap<String, Outline>,
+    icon_dirs: &[PathBuf],
     name: &str,
     data: Key,
     warning_handler: &mut H,
@@ -465,8 +559,27 @@ fn create_button<H: logging::Handler>(
         crate::layout::Label::Text(CString::new(label.as_str())
             .expect("Bad label"))
     } else if let Some(icon) = &button_meta.icon {
-        crate::layout::Label::IconName(CString::new(icon.as_str())
-            .expect("Bad icon"))
+        if is_icon_path(icon) {
+            match find_icon_file(icon, icon_dirs) {
+                Some(path) => crate::layout::Label::IconPath(
+                    CString::new(path.to_string_lossy().as_bytes())
+                        .expect("Bad icon path")
+                ),
+                None => {
+                    warning_handler.handle(
+                        logging::Level::Warning,
+                        &format!(
+                            "Icon file {} of button {} not found in {:?}",
+                            icon, name, icon_dirs,
+                        ),
+                    );
+                    crate::layout::Label::Text(cname.clone())
+                },
+            }
+        } else {
+            crate::layout::Label::IconName(CString::new(icon.as_str())
+                .expect("Bad icon"))
+        }
     } else if let Some(text) = &button_meta.text {
         crate::layout::Label::Text(
             CString::new(text.as_str())
@@ -480,6 +593,15 @@ fn create_button<H: logging::Handler>(
         crate::layout::Label::Text(cname.clone())
     };
 
+    let hint = button_meta.hint_label.as_ref()
+        .and_then(|hint| CString::new(hint.as_str())
+            .or_warn(
+                warning_handler,
+                logging::Problem::Warning,
+                &format!("Hint {} is invalid", hint),
+            )
+        );
+
     let outline_name = match &button_meta.outline {
         Some(outline) => {
             if outlines.contains_key(outline) {
@@ -503,20 +625,66 @@ fn create_button<H: logging::Handler>(
             "No default outline defined! Using 1x1!",
         ).unwrap_or(Outline { width: 1f64, height: 1f64 });
 
+    let classes = button_meta.classes.iter()
+        .filter_map(|class| match is_css_identifier(class) {
+            true => CString::new(class.as_str()).ok(),
+            false => {
+                warning_handler.handle(
+                    logging::Level::Warning,
+                    &format!("Class {} of button {} is not usable in CSS", class, name),
+                );
+                None
+            },
+        })
+        .collect();
+
     layout::Button {
         name: cname,
         outline_name: CString::new(outline_name).expect("Bad outline"),
+        classes,
         // TODO: do layout before creating buttons
         size: layout::Size {
             width: outline.width,
             height: outline.height,
         },
         label: label,
+        hint,
         action: data.action,
         keycodes: data.keycodes,
     }
 }
 
+/// Theme icon names never look like file names
+fn is_icon_path(icon: &str) -> bool {
+    icon.contains('/')
+        || icon.ends_with(".svg")
+        || icon.ends_with(".png")
+}
+
+/// Returns the absolute path, because the renderer has no idea about layout files.
+fn find_icon_file(icon: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let icon = Path::new(icon);
+    if icon.is_absolute() {
+        fs::canonicalize(icon).ok()
+    } else {
+        dirs.iter()
+            .filter_map(|dir| fs::canonicalize(dir.join(icon)).ok())
+            .next()
+    }
+}
+
+/// Only plain names can be matched by a class selector
+fn is_css_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '-' => {},
+        _ => return false,
+    };
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+const CURSOR_KEYSYMS: [&str; 4] = ["Left", "Right", "Up", "Down"];
+
 fn extract_symbol_names<'a>(actions: &'a [(&str, action::Action)])
     -> impl Iterator<Item=String> + 'a
 {
@@ -566,6 +734,7 @@ mod tests {
             Layout::from_file(path_from_root("tests/layout.yaml")).unwrap(),
             Layout {
                 margins: Margins { top: 0f64, bottom: 0f64, side: 0f64 },
+                split_gap: None,
                 views: hashmap!(
                     "base".into() => vec!("test".into()),
                 ),
@@ -578,11 +747,15 @@ mod tests {
                         modifier: None,
                         label: Some("test".into()),
                         outline: None,
+                        classes: Vec::new(),
+                        hint_label: None,
                     }
                 },
                 outlines: hashmap!{
                     "default".into() => Outline { width: 0f64, height: 0f64 }, 
                 },
+                source_dir: path_from_root("tests/layout.yaml")
+                    .parent().map(PathBuf::from),
             }
         );
     }
@@ -711,6 +884,8 @@ mod tests {
                         modifier: None,
                         label: Some("test".into()),
                         outline: None,
+                        classes: Vec::new(),
+                        hint_label: None,
                     }
                 },
                 ".",
@@ -724,6 +899,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_key_hide() {
+        assert_eq!(
+            create_action(
+                &hashmap!{
+                    "hide".into() => ButtonMeta {
+                        action: Some(Action::Hide),
+                        ..Default::default()
+                    }
+                },
+                "hide",
+                Vec::new(),
+                &mut ProblemPanic,
+            ),
+            crate::action::Action::Hide,
+        );
+    }
+
+    #[test]
+    fn test_layout_split() {
+        let first_row_width = |kind| {
+            let layout = Layout::from_resource("us_wide").unwrap()
+                .build_arranged(ProblemPanic, kind).0
+                .unwrap();
+            let (_offset, view) = &layout.views["base"];
+            let (_offset, row) = &view.get_rows()[0];
+            let (gap_start, button) = &row.get_buttons()[4];
+            let (gap_end, _button) = &row.get_buttons()[5];
+            (row.get_size().width, gap_end - (gap_start + button.size.width))
+        };
+        let (wide, no_gap) = first_row_width(layout::ArrangementKind::Wide);
+        let (split, gap) = first_row_width(layout::ArrangementKind::Split);
+        assert_eq!(no_gap, 0.0);
+        assert_eq!(gap, 108.0);
+        assert_eq!(split, wide + gap);
+    }
+
+    #[test]
+    fn test_layout_classes() {
+        let out = Layout::from_file(path_from_root("tests/layout_classes.yaml"))
+            .unwrap()
+            .build(ProblemPanic).0
+            .unwrap();
+        let buttons = out.views["base"].1
+            .get_rows()[0].1
+            .get_buttons();
+        assert_eq!(
+            buttons[0].1.classes,
+            vec![
+                CString::new("punctuation").unwrap(),
+                CString::new("accent").unwrap(),
+            ],
+        );
+        assert_eq!(buttons[1].1.classes, Vec::<CString>::new());
+    }
+
+    #[test]
+    fn test_layout_hint() {
+        let out = Layout::from_file(path_from_root("tests/layout_hint.yaml"))
+            .unwrap()
+            .build(ProblemPanic).0
+            .unwrap();
+        let buttons = out.views["base"].1
+            .get_rows()[0].1
+            .get_buttons();
+        assert_eq!(buttons[0].1.hint, Some(CString::new("!").unwrap()));
+        assert_eq!(buttons[1].1.hint, None);
+    }
+
+    #[test]
+    fn test_layout_icon_file() {
+        struct CountWarnings(u32);
+        impl logging::Handler for CountWarnings {
+            fn handle(&mut self, level: logging::Level, _message: &str) {
+                if let logging::Level::Warning = level {
+                    self.0 += 1;
+                }
+            }
+        }
+
+        let (out, handler) = Layout::from_file(path_from_root("tests/layout_icon.yaml"))
+            .unwrap()
+            .build(CountWarnings(0));
+        let out = out.unwrap();
+        let buttons = out.views["base"].1
+            .get_rows()[0].1
+            .get_buttons();
+        assert_eq!(
+            buttons[0].1.label,
+            crate::layout::Label::IconPath(CString::new(
+                fs::canonicalize(path_from_root("tests/icons/test.svg"))
+                    .unwrap()
+                    .to_string_lossy()
+                    .as_bytes()
+            ).unwrap()),
+        );
+        // Missing files fall back to the name and get reported
+        assert_eq!(
+            buttons[1].1.label,
+            crate::layout::Label::Text(CString::new("b").unwrap()),
+        );
+        assert_eq!(handler.0, 1);
+    }
+
     #[test]
     fn test_layout_margins() {
         let out = Layout::from_file(path_from_root("tests/layout_margins.yaml"))
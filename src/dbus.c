@@ -20,6 +20,9 @@
 
 #include "dbus.h"
 #include "main.h"
+#include "submission.h"
+#include "eek/eek-keyboard.h"
+#include "eekboard/eekboard-context-service.h"
 
 #include <inttypes.h>
 #include <stdio.h>
@@ -64,19 +67,142 @@ handle_set_visible(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
     return TRUE;
 }
 
+static gboolean
+handle_get_visible(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                   gpointer user_data) {
+    (void)user_data;
+    sm_puri_osk0_complete_get_visible(object, invocation,
+                                      sm_puri_osk0_get_visible(object));
+    return TRUE;
+}
+
+static gboolean
+handle_list_layouts(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                    gpointer user_data) {
+    (void)user_data;
+    GVariant *layouts = squeek_list_layouts();
+    sm_puri_osk0_complete_list_layouts(object, invocation, layouts);
+    g_variant_unref(layouts);
+    return TRUE;
+}
+
+static gboolean
+handle_set_layout(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                  const gchar *arg_type, const gchar *arg_name,
+                  gpointer user_data) {
+    DBusHandler *service = user_data;
+    squeek_state_send_layout_set(service->state_manager,
+                                 (char*)arg_name, (char*)arg_type,
+                                 0);
+    sm_puri_osk0_complete_set_layout(object, invocation);
+    return TRUE;
+}
+
+static gboolean
+handle_set_focused_output(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                          const gchar *arg_name, gpointer user_data) {
+    DBusHandler *service = user_data;
+    squeek_state_send_focused_output(service->state_manager, arg_name);
+    sm_puri_osk0_complete_set_focused_output(object, invocation);
+    return TRUE;
+}
+
+static gboolean
+handle_set_overlay(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                   const gchar *arg_name, gpointer user_data) {
+    DBusHandler *service = user_data;
+    if (arg_name[0] == '\0') {
+        g_dbus_method_invocation_return_error(invocation,
+            G_DBUS_ERROR, G_DBUS_ERROR_INVALID_ARGS,
+            "Overlay name empty. Use SetLayout to remove the overlay.");
+        return TRUE;
+    }
+    squeek_state_send_overlay_set(service->state_manager, arg_name);
+    sm_puri_osk0_complete_set_overlay(object, invocation);
+    return TRUE;
+}
+
+static gboolean
+handle_get_view(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                gpointer user_data) {
+    DBusHandler *service = user_data;
+    Layout *keyboard = eekboard_context_service_get_keyboard(service->context);
+    if (!keyboard) {
+        g_dbus_method_invocation_return_error(invocation,
+            G_DBUS_ERROR, G_DBUS_ERROR_FAILED, "No layout loaded");
+        return TRUE;
+    }
+    g_autofree char *view = squeek_layout_get_current_view(keyboard->layout);
+    sm_puri_osk0_complete_get_view(object, invocation, view);
+    return TRUE;
+}
+
+static gboolean
+handle_set_view(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                const gchar *arg_view, gpointer user_data) {
+    DBusHandler *service = user_data;
+    Layout *keyboard = eekboard_context_service_get_keyboard(service->context);
+    if (!keyboard || !squeek_layout_has_view(keyboard->layout, arg_view)) {
+        g_dbus_method_invocation_return_error(invocation,
+            G_DBUS_ERROR, G_DBUS_ERROR_INVALID_ARGS,
+            "No such view: %s", arg_view);
+        return TRUE;
+    }
+    // Goes through the state, to be restored after the layout gets rebuilt.
+    squeek_state_send_view_set(service->state_manager, arg_view);
+    sm_puri_osk0_complete_set_view(object, invocation);
+    return TRUE;
+}
+
+static gboolean
+handle_type_string(SmPuriOSK0 *object, GDBusMethodInvocation *invocation,
+                   const gchar *arg_text, gpointer user_data) {
+    DBusHandler *service = user_data;
+    if (!submission_type_string(service->submission, arg_text)) {
+        g_dbus_method_invocation_return_error(invocation,
+            G_DBUS_ERROR, G_DBUS_ERROR_FAILED,
+            "No text input is active");
+        return TRUE;
+    }
+    sm_puri_osk0_complete_type_string(object, invocation);
+    return TRUE;
+}
+
 DBusHandler *
 dbus_handler_new (GDBusConnection *connection,
                       const gchar     *object_path,
-                  struct squeek_state_manager *state_manager)
+                  struct squeek_state_manager *state_manager,
+                  EekboardContextService *context,
+                  struct submission *submission)
 {
     DBusHandler *self = calloc(1, sizeof(DBusHandler));
     self->object_path = g_strdup(object_path);
     self->connection = connection;
     self->state_manager = state_manager;
+    self->context = context;
+    self->submission = submission;
 
     self->dbus_interface = sm_puri_osk0_skeleton_new();
+    sm_puri_osk0_set_layout(self->dbus_interface, "");
+    sm_puri_osk0_set_overlay(self->dbus_interface, "");
     g_signal_connect(self->dbus_interface, "handle-set-visible",
                      G_CALLBACK(handle_set_visible), self);
+    g_signal_connect(self->dbus_interface, "handle-get-visible",
+                     G_CALLBACK(handle_get_visible), self);
+    g_signal_connect(self->dbus_interface, "handle-list-layouts",
+                     G_CALLBACK(handle_list_layouts), self);
+    g_signal_connect(self->dbus_interface, "handle-set-layout",
+                     G_CALLBACK(handle_set_layout), self);
+    g_signal_connect(self->dbus_interface, "handle-set-overlay",
+                     G_CALLBACK(handle_set_overlay), self);
+    g_signal_connect(self->dbus_interface, "handle-get-view",
+                     G_CALLBACK(handle_get_view), self);
+    g_signal_connect(self->dbus_interface, "handle-set-view",
+                     G_CALLBACK(handle_set_view), self);
+    g_signal_connect(self->dbus_interface, "handle-set-focused-output",
+                     G_CALLBACK(handle_set_focused_output), self);
+    g_signal_connect(self->dbus_interface, "handle-type-string",
+                     G_CALLBACK(handle_type_string), self);
 
     if (self->connection && self->object_path) {
         GError *error = NULL;
@@ -99,3 +225,29 @@ void dbus_handler_set_visible(DBusHandler *service,
 {
     sm_puri_osk0_set_visible(service->dbus_interface, visible);
 }
+
+// Exported to Rust
+void dbus_handler_set_layout(DBusHandler *service,
+                             const char *layout, const char *overlay)
+{
+    // Layouts get reloaded also when only the purpose changes
+    if (g_strcmp0(sm_puri_osk0_get_layout(service->dbus_interface), layout) == 0
+            && g_strcmp0(sm_puri_osk0_get_overlay(service->dbus_interface), overlay) == 0) {
+        return;
+    }
+    sm_puri_osk0_set_layout(service->dbus_interface, layout);
+    sm_puri_osk0_set_overlay(service->dbus_interface, overlay);
+    sm_puri_osk0_emit_layout_changed(service->dbus_interface, layout, overlay);
+}
+
+// Exported to Rust
+void dbus_handler_set_view(DBusHandler *service, const char *view)
+{
+    sm_puri_osk0_emit_view_changed(service->dbus_interface, view);
+}
+
+// Exported to Rust
+void dbus_handler_set_height(DBusHandler *service, uint32_t height)
+{
+    sm_puri_osk0_set_height(service->dbus_interface, height);
+}
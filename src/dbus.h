@@ -20,9 +20,12 @@
 #define DBUS_H_ 1
 
 #include "sm.puri.OSK0.h"
+#include "eek/eek-types.h"
 
 // From main.h
 struct squeek_state_manager;
+// From submission.h
+struct submission;
 
 G_BEGIN_DECLS
 
@@ -43,11 +46,16 @@ typedef struct _DBusHandler
 
     /// Forward incoming events there
     struct squeek_state_manager *state_manager; // shared reference
+    /// Holds the current layout, for view switching
+    EekboardContextService *context; // unowned reference
+    struct submission *submission; // unowned reference
 } DBusHandler;
 
 DBusHandler * dbus_handler_new      (GDBusConnection *connection,
                                              const gchar     *object_path,
-                                     struct squeek_state_manager *state_manager);
+                                     struct squeek_state_manager *state_manager,
+                                     EekboardContextService *context,
+                                     struct submission *submission);
 
 void dbus_handler_destroy(DBusHandler*);
 G_END_DECLS
@@ -4,8 +4,9 @@ use cairo;
 
 use crate::action::{ Action, Modifier };
 use crate::keyboard;
-use crate::layout::{ Button, ButtonPosition, Label, LatchedState, Layout };
+use crate::layout::{ Button, ButtonPosition, Gesture, Label, LatchedState, Layout };
 use crate::layout::c::{ Bounds, EekGtkKeyboard, Point };
+use crate::style;
 use crate::submission::c::Submission as CSubmission;
 
 use glib::translate::FromGlibPtrNone;
@@ -49,6 +50,17 @@ mod c {
             bounds: Bounds,
             icon_name: *const c_char,
             label: *const c_char,
+            text_scale: f64,
+        );
+
+        #[allow(improper_ctypes)]
+        pub fn eek_render_button_hint(
+            renderer: EekRenderer,
+            cr: *mut cairo_sys::cairo_t,
+            ctx: GtkStyleContext,
+            bounds: Bounds,
+            hint: *const c_char,
+            text_scale: f64,
         );
 
         #[allow(improper_ctypes)]
@@ -57,6 +69,7 @@ mod c {
             name: *const c_char,
             outline_name: *const c_char,
             locked_class: *const c_char,
+            classes: *const *const c_char,
             pressed: u64,
         ) -> GtkStyleContext;
 
@@ -65,6 +78,7 @@ mod c {
             ctx: GtkStyleContext,
             outline_name: *const c_char,
             locked_class: *const c_char,
+            classes: *const *const c_char,
         );
     }
 
@@ -82,6 +96,11 @@ mod c {
         let submission = submission.borrow();
         let cr = unsafe { cairo::Context::from_raw_none(cr) };
         let active_modifiers = submission.get_active_modifiers();
+        // Only the space bar works while it's moving the cursor
+        let moving_cursor = match layout.state.gesture {
+            Gesture::Trackpad { .. } => true,
+            _ => false,
+        };
 
         layout.foreach_visible_button(|offset, button, (row, position_in_row)| {
             // TODO: this iterator copies string indices way too much.
@@ -99,14 +118,18 @@ mod c {
                 layout.get_view_latched(),
                 &layout.state.current_view,
             );
+            let dimmed = moving_cursor
+                && state.pressed != keyboard::PressType::Pressed;
             if state.pressed == keyboard::PressType::Pressed
                 || locked != LockedStyle::Free
+                || dimmed
             {
                 render_button_at_position(
                     renderer, &cr,
                     offset,
                     button,
                     state.pressed, locked,
+                    dimmed,
                 );
             }
         })
@@ -129,6 +152,7 @@ mod c {
                 button,
                 keyboard::PressType::Released,
                 LockedStyle::Free,
+                false,
             );
         })
     }
@@ -176,6 +200,7 @@ fn render_button_at_position(
     button: &Button,
     pressed: keyboard::PressType,
     locked: LockedStyle,
+    dimmed: bool,
 ) {
     cr.save();
     cr.translate(position.x, position.y);
@@ -189,9 +214,11 @@ fn render_button_at_position(
         c::eek_renderer_get_scale_factor(renderer)
     };
     let bounds = button.get_bounds();
+    let text_scale = style::get_text_scale();
     let (label_c, icon_name_c) = match &button.label {
         Label::Text(text) => (text.as_ptr(), ptr::null()),
-        Label::IconName(name) => {
+        // The renderer tells paths apart from names
+        Label::IconName(name) | Label::IconPath(name) => {
             let l = unsafe {
                 // CStr doesn't allocate anything, so it only points to
                 // the 'static str, avoiding a memory leak
@@ -206,6 +233,7 @@ fn render_button_at_position(
         button,
         pressed,
         locked,
+        dimmed,
         |ctx| unsafe {
             // TODO: split into separate procedures:
             // draw outline, draw label, draw icon.
@@ -213,10 +241,21 @@ fn render_button_at_position(
                 scale_factor,
                 cairo::Context::to_raw_none(&cr),
                 *ctx,
-                bounds,
+                bounds.clone(),
                 icon_name_c,
                 label_c,
-            )
+                text_scale,
+            );
+            if let Some(hint) = &button.hint {
+                c::eek_render_button_hint(
+                    renderer,
+                    cairo::Context::to_raw_none(&cr),
+                    *ctx,
+                    bounds,
+                    hint.as_ptr(),
+                    text_scale,
+                );
+            }
         }
     );
 
@@ -228,6 +267,7 @@ fn with_button_context<R, F: FnOnce(&c::GtkStyleContext) -> R>(
     button: &Button,
     pressed: keyboard::PressType,
     locked: LockedStyle,
+    dimmed: bool,
     operation: F,
 ) -> R {
     let outline_name_c = button.outline_name.as_ptr();
@@ -240,6 +280,18 @@ fn with_button_context<R, F: FnOnce(&c::GtkStyleContext) -> R>(
             CStr::from_bytes_with_nul_unchecked(b"latched\0").as_ptr()
         },
     };
+    let dimmed_class_c = match dimmed {
+        true => Some(unsafe {
+            CStr::from_bytes_with_nul_unchecked(b"dimmed\0").as_ptr()
+        }),
+        false => None,
+    };
+    // NULL-terminated, pointing into the button
+    let classes_c: Vec<_> = button.classes.iter()
+        .map(|class| class.as_ptr())
+        .chain(dimmed_class_c)
+        .chain(std::iter::once(ptr::null()))
+        .collect();
     
     let ctx = unsafe {
         c::eek_get_style_context_for_button(
@@ -247,6 +299,7 @@ fn with_button_context<R, F: FnOnce(&c::GtkStyleContext) -> R>(
             button.name.as_ptr(),
             outline_name_c,
             locked_class_c,
+            classes_c.as_ptr(),
             pressed as u64,
         )
     };
@@ -258,6 +311,7 @@ fn with_button_context<R, F: FnOnce(&c::GtkStyleContext) -> R>(
             ctx,
             outline_name_c,
             locked_class_c,
+            classes_c.as_ptr(),
         )
     };
 
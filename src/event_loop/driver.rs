@@ -18,7 +18,9 @@
 
 use crate::event_loop;
 use crate::logging;
+use crate::recording;
 use glib;
+use std::fmt;
 use std::sync::mpsc;
 use std::thread;
 use std::time::Instant;
@@ -58,7 +60,7 @@ impl<S> Threaded<S>
 where
     // Not sure why this needs 'static. It's already owned.
     S: ActorState + Send + 'static,
-    S::Event: Send,
+    S::Event: Send + fmt::Debug,
     <S::Outcome as Outcome>::Commands: Send,
 {
     pub fn new(
@@ -99,6 +101,10 @@ where
     ) -> event_loop::State<S> {
         let now = Instant::now();
 
+        recording::with_global(|recorder| recorder.record(
+            recording::Entry::State { event: format!("{:?}", event) }
+        ));
+
         let (new_state, commands) = event_loop::handle_event(state.clone(), event, now);
 
         ui.send(commands)
@@ -0,0 +1,307 @@
+/*
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/*! Gesture typing: turning a path drawn across letters into words.
+ *
+ * Every word from the word list gets an ideal trace,
+ * going straight from one key centre to the next.
+ * Both the drawn path and the traces are resampled to the same number
+ * of evenly spaced points, and the words whose traces stay closest
+ * to the path win.
+ *
+ * Only words starting and ending near where the path starts and ends
+ * are compared, which keeps long word lists usable.
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{ Arc, Mutex };
+use std::thread;
+use crate::layout::c::Point;
+use crate::logging;
+use crate::xdg;
+
+
+/// Points compared between the path and a trace
+const SAMPLES: usize = 32;
+
+/// How much a word loses for being at the end of a ranked list,
+/// in key widths of average distance.
+const RANK_WEIGHT: f64 = 0.2;
+
+pub struct Dictionary {
+    words: Vec<String>,
+    /// More frequent words come first.
+    /// System word lists are alphabetical instead.
+    ranked: bool,
+}
+
+impl Dictionary {
+    /// One word per line.
+    /// Anything after the word on the same line, like a count, is ignored,
+    /// and so are words which can't be glided, like ones with apostrophes.
+    pub fn from_text(text: &str, ranked: bool) -> Dictionary {
+        Dictionary {
+            words: text.lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .filter(|word| word.chars().all(char::is_alphabetic))
+                .map(String::from)
+                .collect(),
+            ranked,
+        }
+    }
+
+    fn load() -> Dictionary {
+        let paths = xdg::data_path("squeekboard/words.txt").into_iter()
+            .map(|path| (path, true))
+            .chain(Some((PathBuf::from("/usr/share/dict/words"), false)));
+        for (path, ranked) in paths {
+            if let Ok(text) = fs::read_to_string(&path) {
+                log_print!(
+                    logging::Level::Info,
+                    "Loaded gesture typing words from {:?}", path,
+                );
+                return Dictionary::from_text(&text, ranked);
+            }
+        }
+        log_print!(
+            logging::Level::Warning,
+            "No word list for gesture typing",
+        );
+        Dictionary::from_text("", false)
+    }
+}
+
+enum Loading {
+    NotStarted,
+    Started,
+    Done(Arc<Dictionary>),
+}
+
+/// Filled in from another thread,
+/// because reading a word list takes a while.
+static DICTIONARY: Mutex<Loading> = Mutex::new(Loading::NotStarted);
+
+/// Starts reading the word list in the background, if not started yet.
+pub fn preload() {
+    let mut dictionary = match DICTIONARY.lock() {
+        Ok(dictionary) => dictionary,
+        Err(_) => {
+            log_print!(logging::Level::Bug, "Word list poisoned");
+            return;
+        },
+    };
+    if let Loading::NotStarted = *dictionary {
+        *dictionary = Loading::Started;
+        thread::spawn(|| {
+            let loaded = Arc::new(Dictionary::load());
+            if let Ok(mut dictionary) = DICTIONARY.lock() {
+                *dictionary = Loading::Done(loaded);
+            }
+        });
+    }
+}
+
+/// Returns None while the word list is still loading.
+pub fn get_dictionary() -> Option<Arc<Dictionary>> {
+    preload();
+    match DICTIONARY.lock().as_deref() {
+        Ok(Loading::Done(dictionary)) => Some(dictionary.clone()),
+        _ => None,
+    }
+}
+
+fn distance(a: &Point, b: &Point) -> f64 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Evenly spaced points along the path
+fn resample(path: &[Point], count: usize) -> Vec<Point> {
+    let length: f64 = path.windows(2)
+        .map(|pair| distance(&pair[0], &pair[1]))
+        .sum();
+    if length == 0.0 {
+        return vec![path[0].clone(); count];
+    }
+    let interval = length / (count - 1) as f64;
+    let mut points = vec![path[0].clone()];
+    // Distance from the last segment start to the next sample
+    let mut wanted = interval;
+    for pair in path.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let segment = distance(from, to);
+        let mut travelled = 0.0;
+        while travelled + wanted <= segment && points.len() < count {
+            travelled += wanted;
+            let ratio = travelled / segment;
+            points.push(Point {
+                x: from.x + (to.x - from.x) * ratio,
+                y: from.y + (to.y - from.y) * ratio,
+            });
+            wanted = interval;
+        }
+        wanted -= segment - travelled;
+    }
+    // Rounding errors could leave the end out
+    while points.len() < count {
+        points.push(path[path.len() - 1].clone());
+    }
+    points
+}
+
+fn find_key(keys: &[(char, Point)], letter: char) -> Option<&Point> {
+    keys.iter()
+        .find(|(key, _centre)| *key == letter)
+        .map(|(_key, centre)| centre)
+}
+
+/// The path going through the keys of the word.
+/// Repeated letters are visited once.
+fn ideal_trace(word: &str, keys: &[(char, Point)]) -> Option<Vec<Point>> {
+    let mut trace: Vec<Point> = Vec::new();
+    for letter in word.chars().flat_map(char::to_lowercase) {
+        let centre = find_key(keys, letter)?;
+        if trace.last() != Some(centre) {
+            trace.push(centre.clone());
+        }
+    }
+    match trace.is_empty() {
+        true => None,
+        false => Some(trace),
+    }
+}
+
+/// Letters whose keys are within reach of the point
+fn letters_near(keys: &[(char, Point)], point: &Point, key_width: f64) -> Vec<char> {
+    keys.iter()
+        .filter(|(_letter, centre)| distance(centre, point) < key_width)
+        .map(|(letter, _centre)| *letter)
+        .collect()
+}
+
+/// Finds the words closest to the path, best first.
+/// `keys` are the centres of letter keys, in lower case,
+/// in the same coordinates as the path.
+pub fn decode(
+    path: &[Point],
+    keys: &[(char, Point)],
+    key_width: f64,
+    dictionary: &Dictionary,
+    count: usize,
+) -> Vec<String> {
+    let (first, last) = match (path.first(), path.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Vec::new(),
+    };
+    // Layouts without letters have nothing to measure by.
+    if key_width <= 0.0 {
+        return Vec::new();
+    }
+    let starts = letters_near(keys, first, key_width);
+    let ends = letters_near(keys, last, key_width);
+    let path = resample(path, SAMPLES);
+    let words = &dictionary.words;
+    let rank_weight = match dictionary.ranked {
+        true => RANK_WEIGHT,
+        false => 0.0,
+    };
+
+    let mut scored: Vec<(f64, &String)> = words.iter().enumerate()
+        .filter(|(_rank, word)| {
+            let mut letters = word.chars().flat_map(char::to_lowercase);
+            let first = letters.next();
+            let last = letters.last().or(first);
+            first.map(|l| starts.contains(&l)).unwrap_or(false)
+                && last.map(|l| ends.contains(&l)).unwrap_or(false)
+        })
+        .filter_map(|(rank, word)| {
+            let trace = resample(&ideal_trace(word, keys)?, SAMPLES);
+            let mean_distance = path.iter().zip(trace.iter())
+                .map(|(a, b)| distance(a, b))
+                .sum::<f64>() / SAMPLES as f64;
+            let score = mean_distance / key_width
+                + rank_weight * rank as f64 / words.len() as f64;
+            Some((score, word))
+        })
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    let mut found: Vec<String> = Vec::new();
+    for (_score, word) in scored {
+        // Lists may contain the same word in different case
+        if !found.iter().any(|w| w.to_lowercase() == word.to_lowercase()) {
+            found.push(word.clone());
+        }
+        if found.len() == count {
+            break;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    /// A single row of keys, 1.0 wide
+    fn make_keys() -> Vec<(char, Point)> {
+        "qwertyuiop".chars().enumerate()
+            .map(|(i, letter)| (letter, point(i as f64 + 0.5, 0.5)))
+            .collect()
+    }
+
+    #[test]
+    fn resample_even() {
+        let points = resample(&[point(0.0, 0.0), point(1.0, 0.0), point(1.0, 1.0)], 5);
+        let expected = [
+            point(0.0, 0.0),
+            point(0.5, 0.0),
+            point(1.0, 0.0),
+            point(1.0, 0.5),
+            point(1.0, 1.0),
+        ];
+        assert_eq!(points.len(), expected.len());
+        for (p, e) in points.iter().zip(expected.iter()) {
+            assert!(distance(p, e) < 1e-9, "{:?} != {:?}", p, e);
+        }
+    }
+
+    #[test]
+    fn word_list() {
+        let dictionary = Dictionary::from_text("the 100\nit's\n\nType\n", true);
+        assert_eq!(dictionary.words, vec!["the".to_string(), "Type".to_string()]);
+    }
+
+    #[test]
+    fn decode_straight() {
+        let dictionary = Dictionary::from_text("type\ntry\nwrite\nquote\n", true);
+        // From t, left to r, right to y
+        let path = [point(4.5, 0.5), point(3.5, 0.6), point(5.5, 0.4)];
+        assert_eq!(
+            decode(&path, &make_keys(), 1.0, &dictionary, 2),
+            vec!["try".to_string()],
+        );
+    }
+
+    #[test]
+    fn decode_no_key_width() {
+        let dictionary = Dictionary::from_text("type\n", true);
+        let path = [point(4.5, 0.5), point(5.5, 0.5)];
+        assert_eq!(decode(&path, &make_keys(), 0.0, &dictionary, 1), Vec::<String>::new());
+    }
+
+    #[test]
+    fn decode_ranks() {
+        let dictionary = Dictionary::from_text("quote\nquite\nqwerty\n", true);
+        let path = [point(0.5, 0.5), point(6.5, 0.5), point(2.5, 0.5)];
+        assert_eq!(
+            decode(&path, &make_keys(), 1.0, &dictionary, 3),
+            vec!["quite".to_string(), "quote".to_string()],
+        );
+    }
+}
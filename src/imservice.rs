@@ -11,6 +11,7 @@ use std::string::String;
 use std::time::Instant;
 
 use crate::main;
+use crate::recording;
 use crate::state;
 use crate::state::Event;
 use crate::logging;
@@ -244,6 +245,16 @@ pub enum ContentPurpose {
     Terminal = 13,
 }
 
+impl ContentPurpose {
+    /// Typing in such fields must not be exposed
+    pub fn is_sensitive(&self) -> bool {
+        match self {
+            ContentPurpose::Password | ContentPurpose::Pin => true,
+            _ => false,
+        }
+    }
+}
+
 // Utilities from ::logging need a printable error type
 pub struct UnrecognizedValue;
 
@@ -319,6 +330,24 @@ impl Default for IMProtocolState {
     }
 }
 
+/// Typing in such fields must not be exposed
+pub fn is_sensitive_field(purpose: ContentPurpose, hint: ContentHint) -> bool {
+    purpose.is_sensitive()
+        || hint.intersects(ContentHint::SENSITIVE_DATA | ContentHint::HIDDEN_TEXT)
+}
+
+/// Receives text meant for the focused text field
+pub trait InputMethodSink {
+    fn commit_string(&self, text: &CString) -> Result<(), SubmitError>;
+    fn commit(&mut self) -> Result<(), SubmitError>;
+    /// Lengths are in bytes
+    fn delete_surrounding_text(&self, before: u32, after: u32)
+        -> Result<(), SubmitError>;
+    fn is_active(&self) -> bool;
+    /// The text field holds secrets, like passwords
+    fn is_sensitive(&self) -> bool;
+}
+
 pub struct IMService {
     /// Owned reference (still created and destroyed in C)
     pub im: c::InputMethod,
@@ -330,6 +359,7 @@ pub struct IMService {
     serial: Wrapping<u32>,
 }
 
+#[derive(Debug)]
 pub enum SubmitError {
     /// The input method had not been activated
     NotActive,
@@ -359,7 +389,33 @@ impl IMService {
         imservice
     }
 
-    pub fn commit_string(&self, text: &CString) -> Result<(), SubmitError> {
+    fn send_event(&self) {
+        let state = &self.current;
+        recording::with_global(|recorder| recorder.record(
+            recording::Entry::InputMethod {
+                active: state.active,
+                purpose: state.content_purpose as u32,
+                hint: state.content_hint.bits(),
+            }
+        ));
+        let timestamp = Instant::now();
+        let message = if state.active {
+            state::InputMethod::Active(
+                state::InputMethodDetails {
+                    hint: state.content_hint,
+                    purpose: state.content_purpose,
+                }
+            )
+        } else {
+            state::InputMethod::InactiveSince(timestamp)
+        };
+        self.sender.send(Event::InputMethod(message))
+            .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
+    }
+}
+
+impl InputMethodSink for IMService {
+    fn commit_string(&self, text: &CString) -> Result<(), SubmitError> {
         match self.current.active {
             true => {
                 unsafe {
@@ -371,17 +427,11 @@ impl IMService {
         }
     }
 
-    pub fn delete_surrounding_text(
-        &self,
-        before: u32, after: u32,
-    ) -> Result<(), SubmitError> {
+    fn commit(&mut self) -> Result<(), SubmitError> {
         match self.current.active {
             true => {
                 unsafe {
-                    c::eek_input_method_delete_surrounding_text(
-                        self.im,
-                        before, after,
-                    )
+                    c::eek_input_method_commit(self.im, self.serial.0)
                 }
                 Ok(())
             },
@@ -389,11 +439,17 @@ impl IMService {
         }
     }
 
-    pub fn commit(&mut self) -> Result<(), SubmitError> {
+    fn delete_surrounding_text(
+        &self,
+        before: u32, after: u32,
+    ) -> Result<(), SubmitError> {
         match self.current.active {
             true => {
                 unsafe {
-                    c::eek_input_method_commit(self.im, self.serial.0)
+                    c::eek_input_method_delete_surrounding_text(
+                        self.im,
+                        before, after,
+                    )
                 }
                 Ok(())
             },
@@ -401,24 +457,13 @@ impl IMService {
         }
     }
 
-    pub fn is_active(&self) -> bool {
+    fn is_active(&self) -> bool {
         self.current.active
     }
 
-    fn send_event(&self) {
+    fn is_sensitive(&self) -> bool {
         let state = &self.current;
-        let timestamp = Instant::now();
-        let message = if state.active {
-            state::InputMethod::Active(
-                state::InputMethodDetails {
-                    hint: state.content_hint,
-                    purpose: state.content_purpose,
-                }
-            )
-        } else {
-            state::InputMethod::InactiveSince(timestamp)
-        };
-        self.sender.send(Event::InputMethod(message))
-            .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
+        state.active
+            && is_sensitive_field(state.content_purpose, state.content_hint)
     }
 }
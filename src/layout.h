@@ -15,6 +15,7 @@
 enum squeek_arrangement_kind {
     ARRANGEMENT_KIND_BASE = 0,
     ARRANGEMENT_KIND_WIDE = 1,
+    ARRANGEMENT_KIND_SPLIT = 2,
 };
 
 struct squeek_layout_state {
@@ -34,6 +35,11 @@ struct transformation squeek_layout_calculate_transformation(
 
 struct squeek_layout *squeek_load_layout(const char *name, uint32_t type, uint32_t variant_type, const char *overlay_name);
 enum squeek_arrangement_kind squeek_layout_get_kind(const struct squeek_layout *);
+/// Free with g_free
+char *squeek_layout_get_current_view(const struct squeek_layout *);
+uint8_t squeek_layout_has_view(const struct squeek_layout *, const char *name);
+/// Returns 0 if the view doesn't exist
+uint8_t squeek_layout_set_view(struct squeek_layout *, const char *name);
 uint32_t squeek_layout_get_purpose(const struct squeek_layout *);
 void squeek_layout_free(struct squeek_layout*);
 
@@ -44,6 +50,14 @@ void squeek_layout_release(struct squeek_layout *layout,
                            struct squeek_popover *popover,
                            struct squeek_state_manager *state,
                            EekGtkKeyboard *ui_keyboard);
+void squeek_layout_long_press(struct squeek_layout *layout,
+                              struct submission *submission,
+                              struct transformation widget_to_layout,
+                              uint32_t timestamp,
+                              struct squeek_popover *popover,
+                              struct squeek_state_manager *state,
+                              EekGtkKeyboard *ui_keyboard);
+uint32_t squeek_layout_get_long_press_ms(void);
 void squeek_layout_release_all_only(struct squeek_layout *layout,
                                     struct submission *submission,
                                     uint32_t timestamp);
@@ -17,20 +17,28 @@
  * and let the renderer scale and center it within the widget.
  */
 
+use std::cell::RefCell;
 use std::cmp;
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ffi::{ CStr, CString };
 use std::fmt;
+use std::rc::Rc;
 use std::vec::Vec;
 
 use crate::action::Action;
 use crate::actors;
+use crate::alternatives;
 use crate::drawing;
 use crate::float_ord::FloatOrd;
+use crate::glide;
 use crate::keyboard::{KeyState, KeyCode, PressType};
 use crate::logging;
 use crate::popover;
+use crate::preview;
 use crate::receiver;
+use crate::recording;
+use crate::settings;
+use crate::state;
 use crate::submission::{ Submission, SubmitData, Timestamp };
 use crate::util::find_max_double;
 
@@ -47,10 +55,13 @@ pub mod c {
     use crate::submission::c::Submission as CSubmission;
 
     use gtk_sys;
+    use std::ffi::CStr;
     use std::ops::{ Add, Sub };
-    use std::os::raw::c_void;
+    use std::os::raw::{ c_char, c_void };
     
     use crate::util::CloneOwned;
+
+    use glib::translate::ToGlibPtr;
     
     // The following defined in C
     #[repr(transparent)]
@@ -179,10 +190,13 @@ pub mod c {
         allocation_height: f64,
     ) -> Transformation {
         let layout = unsafe { &*layout };
-        layout.shape.calculate_transformation(Size {
-            width: allocation_width,
-            height: allocation_height,
-        })
+        layout.shape.calculate_one_handed_transformation(
+            Size {
+                width: allocation_width,
+                height: allocation_height,
+            },
+            &settings::get_one_handed(),
+        )
     }
 
     #[no_mangle]
@@ -199,6 +213,56 @@ pub mod c {
         layout.shape.purpose.clone() as u32
     }
 
+    /// Returns a copy to be freed with g_free
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_layout_get_current_view(layout: *const Layout) -> *const c_char {
+        let layout = unsafe { &*layout };
+        layout.state.current_view.as_str().to_glib_full()
+    }
+
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_layout_has_view(layout: *const Layout, name: *const c_char) -> u8 {
+        let layout = unsafe { &*layout };
+        let name = unsafe { CStr::from_ptr(name) };
+        name.to_str()
+            .map(|name| layout.shape.views.contains_key(name))
+            .unwrap_or(false) as u8
+    }
+
+    /// Switches views directly, dropping any latch.
+    /// Returns 0 if the view doesn't exist.
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_layout_set_view(layout: *mut Layout, name: *const c_char) -> u8 {
+        let layout = unsafe { &mut *layout };
+        let name = unsafe { CStr::from_ptr(name) };
+        let result = name.to_str()
+            .map_err(|_| NoSuchView)
+            .and_then(|name| crate::layout::procedures::set_view(
+                layout,
+                name.into(),
+            ));
+        match result {
+            Ok(()) => {
+                recording::with_global(|recorder| recorder.record(
+                    recording::Entry::View {
+                        name: layout.state.current_view.clone(),
+                    }
+                ));
+                1
+            },
+            Err(e) => {
+                log_print!(
+                    logging::Level::Warning,
+                    "Can't switch to view {:?}: {}", name, e,
+                );
+                0
+            },
+        }
+    }
+
     #[no_mangle]
     pub extern "C"
     fn squeek_layout_free(layout: *mut Layout) {
@@ -221,35 +285,72 @@ pub mod c {
             app_state: receiver::c::State,
             ui_keyboard: EekGtkKeyboard,
         ) {
-            let time = Timestamp(time);
             let layout = unsafe { &mut *layout };
-            let submission = submission.clone_ref();
-            let mut submission = submission.borrow_mut();
+            let submission_ref = submission.clone_ref();
+            let mut submission = submission_ref.borrow_mut();
             let app_state = app_state.clone_owned();
             let popover_state = popover.clone_owned();
             
             let ui_backend = UIBackend {
                 widget_to_layout,
                 keyboard: ui_keyboard,
+                submission: submission_ref.clone(),
             };
 
-            // The list must be copied,
-            // because it will be mutated in the loop
-            let pressed_buttons
-                = layout.state.active_buttons.clone();
-            for (button, _key_state) in pressed_buttons.iter_pressed() {
-                seat::handle_release_key(
-                    layout,
-                    &mut submission,
-                    Some(&ui_backend),
-                    time,
-                    Some((&popover_state, app_state.clone())),
-                    button,
-                );
-            }
+            crate::layout::procedures::release(
+                layout,
+                &mut submission,
+                Some(&ui_backend),
+                Some((&popover_state, app_state)),
+                Timestamp(time),
+            );
+            // Another finger may still be down.
+            crate::layout::procedures::update_preview(
+                layout,
+                &submission,
+                &ui_backend,
+                settings::get_key_preview(),
+            );
             drawing::queue_redraw(ui_keyboard);
         }
 
+        /// Called by a timer started on press,
+        /// to react to a key held down long enough.
+        #[no_mangle]
+        pub extern "C"
+        fn squeek_layout_long_press(
+            layout: *mut Layout,
+            submission: CSubmission,
+            widget_to_layout: Transformation,
+            time: u32,
+            popover: actors::popover::c::Actor,
+            app_state: receiver::c::State,
+            ui_keyboard: EekGtkKeyboard,
+        ) {
+            let layout = unsafe { &mut *layout };
+            let submission_ref = submission.clone_ref();
+            let mut submission = submission_ref.borrow_mut();
+            let app_state = app_state.clone_owned();
+            let popover_state = popover.clone_owned();
+
+            let ui_backend = UIBackend {
+                widget_to_layout,
+                keyboard: ui_keyboard,
+                submission: submission_ref.clone(),
+            };
+
+            let opened = crate::layout::procedures::open_held_chooser(
+                layout,
+                &mut submission,
+                Some(&ui_backend),
+                Some((&popover_state, app_state)),
+                Timestamp(time),
+            );
+            if opened {
+                drawing::queue_redraw(ui_keyboard);
+            }
+        }
+
         /// Release all buttons but don't redraw
         #[no_mangle]
         pub extern "C"
@@ -261,19 +362,16 @@ pub mod c {
             let layout = unsafe { &mut *layout };
             let submission = submission.clone_ref();
             let mut submission = submission.borrow_mut();
-            // The list must be copied,
-            // because it will be mutated in the loop
-            let pressed_buttons = layout.state.active_buttons.clone();
-            for (button, _key_state) in pressed_buttons.iter_pressed() {
-                seat::handle_release_key(
-                    layout,
-                    &mut submission,
-                    None, // don't update UI
-                    Timestamp(time),
-                    None, // don't switch layouts
-                    button,
-                );
-            }
+            crate::layout::procedures::release(
+                layout,
+                &mut submission,
+                None, // don't update UI
+                None, // don't switch layouts
+                Timestamp(time),
+            );
+            // The panel is going away, the bubbles must not stay behind.
+            preview::hide();
+            alternatives::hide();
         }
 
         #[no_mangle]
@@ -287,37 +385,40 @@ pub mod c {
             ui_keyboard: EekGtkKeyboard,
         ) {
             let layout = unsafe { &mut *layout };
-            let submission = submission.clone_ref();
-            let mut submission = submission.borrow_mut();
+            let submission_ref = submission.clone_ref();
+            let mut submission = submission_ref.borrow_mut();
             let point = widget_to_layout.forward(
                 Point { x: x_widget, y: y_widget }
             );
-
-            let index = layout.find_index_by_position(point);
-
-            if let Some((row, position_in_row)) = index {
-                let button = ButtonPosition {
-                    view: layout.state.current_view.clone(),
-                    row,
-                    position_in_row,
-                };
-                seat::handle_press_key(
-                    layout,
-                    &mut submission,
-                    Timestamp(time),
-                    &button,
-                );
+            // Typing on means the glided word is accepted.
+            alternatives::hide();
+
+            let pressed = crate::layout::procedures::press(
+                layout,
+                &mut submission,
+                point,
+                Timestamp(time),
+            );
+            let ui_backend = UIBackend {
+                widget_to_layout,
+                keyboard: ui_keyboard,
+                submission: submission_ref.clone(),
+            };
+            crate::layout::procedures::update_preview(
+                layout,
+                &submission,
+                &ui_backend,
+                settings::get_key_preview(),
+            );
+            if pressed {
                 // maybe TODO: draw on the display buffer here
                 drawing::queue_redraw(ui_keyboard);
                 unsafe {
                     eek_gtk_keyboard_emit_feedback(ui_keyboard);
                 }
-            };
+            }
         }
 
-        // FIXME: this will work funny
-        // when 2 touch points are on buttons and moving one after another
-        // Solution is to have separate pressed lists for each point
         #[no_mangle]
         pub extern "C"
         fn squeek_layout_drag(
@@ -330,10 +431,9 @@ pub mod c {
             app_state: receiver::c::State,
             ui_keyboard: EekGtkKeyboard,
         ) {
-            let time = Timestamp(time);
             let layout = unsafe { &mut *layout };
-            let submission = submission.clone_ref();
-            let mut submission = submission.borrow_mut();
+            let submission_ref = submission.clone_ref();
+            let mut submission = submission_ref.borrow_mut();
             // We only need to query state here, not update.
             // A copy is enough.
             let popover_state = popover.clone_owned();
@@ -341,64 +441,38 @@ pub mod c {
             let ui_backend = UIBackend {
                 widget_to_layout,
                 keyboard: ui_keyboard,
+                submission: submission_ref.clone(),
             };
             let point = ui_backend.widget_to_layout.forward(
                 Point { x: x_widget, y: y_widget }
             );
 
-            let pressed_buttons = layout.state.active_buttons.clone();
-            let pressed_buttons = pressed_buttons.iter_pressed();
-            let button_info = layout.find_index_by_position(point);
-
-            if let Some((row, position_in_row)) = button_info {
-                let current_pos = ButtonPosition {
-                    view: layout.state.current_view.clone(),
-                    row,
-                    position_in_row,
-                };
-                let mut found = false;
-                for (button, _key_state) in pressed_buttons {
-                    if button == &current_pos {
-                        found = true;
-                    } else {
-                        seat::handle_release_key(
-                            layout,
-                            &mut submission,
-                            Some(&ui_backend),
-                            time,
-                            Some((&popover_state, app_state.clone())),
-                            button,
-                        );
-                    }
-                }
-                if !found {
-                    let button = ButtonPosition {
-                        view: layout.state.current_view.clone(),
-                        row,
-                        position_in_row,
-                    };
-                    seat::handle_press_key(
-                        layout,
-                        &mut submission,
-                        time,
-                        &button,
-                    );
-                    // maybe TODO: draw on the display buffer here
-                    unsafe {
-                        eek_gtk_keyboard_emit_feedback(ui_keyboard);
+            let outcome = crate::layout::procedures::drag(
+                layout,
+                &mut submission,
+                Some(&ui_backend),
+                Some((&popover_state, app_state)),
+                point,
+                Timestamp(time),
+            );
+            crate::layout::procedures::update_preview(
+                layout,
+                &submission,
+                &ui_backend,
+                settings::get_key_preview(),
+            );
+            use crate::layout::procedures::DragOutcome;
+            match outcome {
+                DragOutcome::Ignored => return,
+                DragOutcome::Hidden => {},
+                DragOutcome::Moved { pressed } => {
+                    if pressed {
+                        // maybe TODO: draw on the display buffer here
+                        unsafe {
+                            eek_gtk_keyboard_emit_feedback(ui_keyboard);
+                        }
                     }
-                }
-            } else {
-                for (button, _key_state) in pressed_buttons {
-                    seat::handle_release_key(
-                        layout,
-                        &mut submission,
-                        Some(&ui_backend),
-                        time,
-                        Some((&popover_state, app_state.clone())),
-                        button,
-                    );
-                }
+                },
             }
             drawing::queue_redraw(ui_keyboard);
         }
@@ -440,6 +514,8 @@ pub enum Label {
     Text(CString),
     /// Icon name used to render the symbol
     IconName(CString),
+    /// Absolute path of an image file, rendered like a symbolic icon
+    IconPath(CString),
 }
 
 /// The definition of an interactive button
@@ -449,9 +525,13 @@ pub struct Button {
     pub name: CString,
     /// Label to display to the user
     pub label: Label,
+    /// Small secondary label, like a character available elsewhere
+    pub hint: Option<CString>,
     pub size: Size,
     /// The name of the visual class applied
     pub outline_name: CString,
+    /// Additional CSS classes from the layout
+    pub classes: Vec<CString>,
     // action-related stuff
     /// A cache of raw keycodes derived from Action::Submit given a keymap
     pub keycodes: Vec<KeyCode>,
@@ -513,7 +593,8 @@ impl Row {
     /// Finds the first button that covers the specified point
     /// relative to row's position's origin.
     /// Returns its index too.
-    fn find_button_by_position(&self, x: f64) -> (&Button, usize)
+    /// Returns None inside gaps between buttons, like in split layouts.
+    fn find_button_by_position(&self, x: f64) -> Option<(&Button, usize)>
     {
         // Buttons are sorted so we can use a binary search to find the clicked
         // button. Note this doesn't check whether the point is actually within
@@ -526,7 +607,13 @@ impl Row {
         let index = result.unwrap_or_else(|r| r);
         let index = if index > 0 { index - 1 } else { 0 };
 
-        (&self.buttons[index].1, index)
+        let (offset, button) = &self.buttons[index];
+        let in_gap = index + 1 < self.buttons.len()
+            && x >= offset + button.size.width;
+        match in_gap {
+            true => None,
+            false => Some((button, index)),
+        }
     }
 }
 
@@ -603,13 +690,8 @@ impl View {
         let index = if index > 0 { index - 1 } else { 0 };
 
         let row = &self.rows[index];
-        let (button, button_index)
-            = row.1.find_button_by_position(point.x - row.0.x);
-
-        Some((
-            &button,
-            (index, button_index),
-        ))
+        row.1.find_button_by_position(point.x - row.0.x)
+            .map(|(button, button_index)| (button, (index, button_index)))
     }
 
     pub fn get_size(&self) -> Size {
@@ -642,6 +724,8 @@ impl View {
 pub enum ArrangementKind {
     Base = 0,
     Wide = 1,
+    /// Wide, with a gap in the middle for thumb typing on tablets
+    Split = 2,
 }
 
 #[derive(Debug, PartialEq)]
@@ -713,6 +797,28 @@ impl ActiveButtons {
     }
 }
 
+/// Progress of the gesture performed by the current touch point
+#[derive(Clone, Debug, PartialEq)]
+pub enum Gesture {
+    /// No touch point is down.
+    Idle,
+    /// The touch point went down at this position, in layout coordinates.
+    Started(c::Point),
+    /// A word is being glided across letters,
+    /// along this path in layout coordinates.
+    Glide(Vec<c::Point>),
+    /// The space bar is moving the cursor.
+    Trackpad {
+        /// Arrow keys were sent for the distance up to this point.
+        last: c::Point,
+        /// Any arrow keys were sent at all.
+        moved: bool,
+    },
+    /// The gesture was recognized,
+    /// and the touch point gets ignored until it's released.
+    Consumed,
+}
+
 /// Changeable state that can't be derived from the definition of the layout.
 pub struct LayoutState {
     pub current_view: String,
@@ -733,6 +839,13 @@ pub struct LayoutState {
     /// Latched/locked appearance is derived from current view
     /// and button metadata.
     pub active_buttons: ActiveButtons,
+    /// When the currently pressed buttons went down,
+    /// to tell apart long presses.
+    pressed_since: HashMap<ButtonPosition, Timestamp>,
+    pub gesture: Gesture,
+    /// The space bar or a letter, held down without typing yet.
+    /// It types on release, unless it started a gesture.
+    held_back: Option<ButtonPosition>,
 }
 
 /// A builder structure for picking up layout data from storage
@@ -743,6 +856,16 @@ pub struct LayoutParseData {
     /// xkb keymaps applicable to the contained keys
     pub keymaps: Vec<CString>,
     pub margins: Margins,
+    pub cursor_keys: CursorKeys,
+}
+
+/// Arrow keys, present in the keymaps of every layout
+#[derive(Clone, Debug)]
+pub struct CursorKeys {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
 }
 
 /// Static, cacheable information for the layout
@@ -760,10 +883,13 @@ pub struct LayoutData {
     // Non-UI stuff
     /// xkb keymaps applicable to the contained keys. Unchangeable
     pub keymaps: Vec<CString>,
+    /// Sent when the space bar moves the cursor.
+    /// Without them, the space bar is only a key.
+    pub cursor_keys: Option<CursorKeys>,
 }
 
 #[derive(Debug)]
-struct NoSuchView;
+pub struct NoSuchView;
 
 impl fmt::Display for NoSuchView {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -828,6 +954,32 @@ impl LayoutData {
             scale_y: 1.0,
         })
     }
+
+    /// In one-handed mode, shrinks the layout to fit a narrower width
+    /// and docks it to one side, centred vertically.
+    /// Keys keep their proportions.
+    pub fn calculate_one_handed_transformation(
+        &self,
+        available: Size,
+        one_handed: &settings::OneHanded,
+    ) -> c::Transformation {
+        if !one_handed.enabled {
+            return self.calculate_transformation(available);
+        }
+        let width = available.width * one_handed.width;
+        let compact = self.calculate_transformation(Size {
+            width,
+            height: available.height,
+        });
+        let offset = match one_handed.side {
+            settings::Side::Left => 0.0,
+            settings::Side::Right => available.width - width,
+        };
+        c::Transformation {
+            origin_x: offset + compact.origin_x,
+            ..compact
+        }
+    }
 }
 
 // Unfortunately, changes are not atomic due to mutability :(
@@ -843,11 +995,15 @@ impl Layout {
                 keymaps: data.keymaps,
                 margins: data.margins,
                 purpose,
+                cursor_keys: Some(data.cursor_keys),
             },
             state: LayoutState {
                 current_view: "base".to_owned(),
                 view_latched: LatchedState::Not,
                 active_buttons: ActiveButtons(HashMap::new()),
+                pressed_since: HashMap::new(),
+                gesture: Gesture::Idle,
+                held_back: None,
             },
         }
     }
@@ -857,6 +1013,55 @@ impl Layout {
             .get(&self.state.current_view).expect("Selected nonexistent view")
     }
 
+    /// Centres of the letter keys of the current view in layout coordinates,
+    /// in lower case, and their average width.
+    pub fn get_letter_keys(&self) -> (Vec<(char, c::Point)>, f64) {
+        let mut keys = Vec::new();
+        let mut total_width = 0.0;
+        self.foreach_visible_button(|offset, button, _index| {
+            if let Some(letter) = button.action.get_letter() {
+                let centre = c::Point {
+                    x: offset.x + button.size.width / 2.0,
+                    y: offset.y + button.size.height / 2.0,
+                };
+                total_width += button.size.width;
+                keys.extend(letter.to_lowercase().map(|l| (l, centre.clone())));
+            }
+        });
+        let width = match keys.len() {
+            0 => 1.0,
+            count => total_width / count as f64,
+        };
+        (keys, width)
+    }
+
+    /// The only button pressed in the current view, to show above the finger,
+    /// with its area in layout coordinates.
+    /// Icons are not enlarged.
+    pub fn find_preview(&self) -> Option<(c::Bounds, CString)> {
+        let mut pressed = self.state.active_buttons.iter_pressed()
+            .filter(|(position, _state)| position.view == self.state.current_view);
+        let (position, _state) = pressed.next()?;
+        if pressed.next().is_some() {
+            // Several fingers are down, none of them stands out.
+            return None;
+        }
+        let (view_offset, _view) = self.get_current_view_position();
+        let (place, button) = self.shape.find_button_place(position)?;
+        match &button.label {
+            Label::Text(text) => Some((
+                c::Bounds {
+                    x: view_offset.x + place.x,
+                    y: view_offset.y + place.y,
+                    width: button.size.width,
+                    height: button.size.height,
+                },
+                text.clone(),
+            )),
+            _ => None,
+        }
+    }
+
     pub fn get_current_view(&self) -> &View {
         &self.shape.views.get(&self.state.current_view).expect("Selected nonexistent view").1
     }
@@ -1010,7 +1215,7 @@ fn try_set_view(layout: &mut Layout, view_name: &str) {
 }
 
 
-mod procedures {
+pub mod procedures {
     use super::*;
 
     pub type Place<'v> = (c::Point, &'v Button);
@@ -1029,72 +1234,894 @@ mod procedures {
         ))
     }
 
-    #[cfg(test)]
-    mod test {
-        use super::*;
+    /// Shows the only pressed button above the finger, or hides the preview.
+    /// Nothing is shown while typing secrets.
+    pub fn update_preview(
+        layout: &Layout,
+        submission: &Submission,
+        ui: &dyn UI,
+        enabled: bool,
+    ) {
+        let preview = match enabled && !submission.is_private() {
+            true => layout.find_preview(),
+            false => None,
+        };
+        match preview {
+            Some((area, label)) => ui.show_preview(area, &label),
+            None => ui.hide_preview(),
+        }
+    }
 
-        use crate::layout::test::*;
+    /// Presses the button under the touch point, if any.
+    /// Returns true if a button got pressed.
+    pub fn press(
+        layout: &mut Layout,
+        submission: &mut Submission,
+        point: c::Point,
+        time: Timestamp,
+    ) -> bool {
+        submission.record(recording::Entry::Press {
+            x: point.x,
+            y: point.y,
+            time: time.0,
+        });
+        // Another finger going down ends whatever the last one was doing.
+        seat::flush_held_back(layout, submission, time);
+        layout.state.gesture = Gesture::Started(point.clone());
 
-        /// Checks indexing of buttons
-        #[test]
-        fn view_has_button() {
-            let button = make_button("1".into());
+        match layout.find_index_by_position(point) {
+            Some((row, position_in_row)) => {
+                let button = ButtonPosition {
+                    view: layout.state.current_view.clone(),
+                    row,
+                    position_in_row,
+                };
+                seat::handle_press_key(layout, submission, time, &button);
+                true
+            },
+            None => false,
+        }
+    }
 
-            let row = Row::new(vec!((0.1, button)));
+    pub enum DragOutcome {
+        /// The touch point belongs to a finished gesture
+        Ignored,
+        /// The swipe hiding the panel got completed
+        Hidden,
+        Moved {
+            /// A new button got pressed
+            pressed: bool,
+        },
+    }
 
-            let view = View::new(vec!((1.2, row)));
+    // FIXME: this will work funny
+    // when 2 touch points are on buttons and moving one after another
+    // Solution is to have separate pressed lists for each point
+    pub fn drag(
+        layout: &mut Layout,
+        submission: &mut Submission,
+        ui: Option<&dyn UI>,
+        manager: Option<(&actors::popover::State, receiver::State)>,
+        point: c::Point,
+        time: Timestamp,
+    ) -> DragOutcome {
+        submission.record(recording::Entry::Drag {
+            x: point.x,
+            y: point.y,
+            time: time.0,
+        });
+        let get_manager = || manager.as_ref()
+            .map(|(popover, app_state)| (*popover, app_state.clone()));
 
-            assert_eq!(
-                find_button_place(&view, (0, 0)),
-                Some((
-                    c::Point { x: 0.1, y: 1.2 },
-                    &make_button("1".into()),
-                ))
-            );
+        if open_held_chooser(layout, submission, ui, get_manager(), time) {
+            return DragOutcome::Moved { pressed: false };
+        }
 
-            let view = View::new(vec![]);
-            assert_eq!(
-                find_button_place(&view, (0, 0)),
-                None,
-            );
+        if let Gesture::Started(start) = &layout.state.gesture {
+            if starts_trackpad(layout, start, &point, time) {
+                layout.state.gesture = Gesture::Trackpad {
+                    last: start.clone(),
+                    moved: false,
+                };
+            } else if starts_glide(layout, submission, start, &point) {
+                layout.state.gesture = Gesture::Glide(vec![start.clone()]);
+            }
         }
-    }
-}
 
-pub struct UIBackend {
-    widget_to_layout: c::Transformation,
-    keyboard: c::EekGtkKeyboard,
-}
+        if let Gesture::Glide(path) = &mut layout.state.gesture {
+            path.push(point);
+            // Buttons along the path don't get pressed.
+            return DragOutcome::Moved { pressed: false };
+        }
 
-/// Top level procedures, dispatching to everything
-mod seat {
-    use super::*;
+        match &layout.state.gesture {
+            Gesture::Consumed => return DragOutcome::Ignored,
+            Gesture::Trackpad { last, moved } => {
+                let step = get_trackpad_step(layout);
+                if let (Some(step), Some(keys)) = (step, &layout.shape.cursor_keys) {
+                    let steps = count_steps(last, &point, step);
+                    send_cursor_keys(submission, keys, steps, time);
+                    layout.state.gesture = Gesture::Trackpad {
+                        last: c::Point {
+                            x: last.x + steps.0 as f64 * step,
+                            y: last.y + steps.1 as f64 * step,
+                        },
+                        moved: *moved || steps != (0, 0),
+                    };
+                }
+                // The space bar stays pressed even when the finger leaves it.
+                return DragOutcome::Moved { pressed: false };
+            },
+            Gesture::Started(start) => {
+                let is_hiding = is_hide_swipe(
+                    start,
+                    &point,
+                    layout.shape.calculate_size().height,
+                    settings::get_hide_swipe(),
+                );
+                if is_hiding {
+                    layout.state.gesture = Gesture::Consumed;
+                    let pressed_buttons = layout.state.active_buttons.clone();
+                    for (button, _key_state) in pressed_buttons.iter_pressed() {
+                        seat::drop_key(layout, submission, time, button);
+                    }
+                    if let Some((_popover, app_state)) = get_manager() {
+                        app_state
+                            .send(state::Event::Visibility(
+                                state::visibility::Event::ForceHidden,
+                            ))
+                            .or_print(
+                                logging::Problem::Warning,
+                                "Can't send to state manager",
+                            );
+                    }
+                    return DragOutcome::Hidden;
+                }
+                if may_become_hide_swipe(start, &point, settings::get_hide_swipe()) {
+                    // Keys under the path would get typed before the swipe is recognized.
+                    return DragOutcome::Moved { pressed: false };
+                }
+            },
+            Gesture::Idle | Gesture::Glide(_) => {},
+        }
 
-    fn handle_press_key_cleaner(
-        shape: &LayoutData,
+        let pressed_buttons = layout.state.active_buttons.clone();
+        let pressed_buttons = pressed_buttons.iter_pressed();
+        let button_info = layout.find_index_by_position(point);
+
+        if let Some((row, position_in_row)) = button_info {
+            let current_pos = ButtonPosition {
+                view: layout.state.current_view.clone(),
+                row,
+                position_in_row,
+            };
+            let mut found = false;
+            for (button, _key_state) in pressed_buttons {
+                if button == &current_pos {
+                    found = true;
+                } else {
+                    seat::handle_release_key(
+                        layout,
+                        submission,
+                        ui,
+                        time,
+                        get_manager(),
+                        button,
+                    );
+                }
+            }
+            if !found {
+                let button = ButtonPosition {
+                    view: layout.state.current_view.clone(),
+                    row,
+                    position_in_row,
+                };
+                seat::handle_press_key(layout, submission, time, &button);
+            }
+            DragOutcome::Moved { pressed: !found }
+        } else {
+            for (button, _key_state) in pressed_buttons {
+                seat::handle_release_key(
+                    layout,
+                    submission,
+                    ui,
+                    time,
+                    get_manager(),
+                    button,
+                );
+            }
+            DragOutcome::Moved { pressed: false }
+        }
+    }
+
+    /// Releases all pressed buttons.
+    /// The popover and layout switching need the `manager`.
+    pub fn release(
+        layout: &mut Layout,
         submission: &mut Submission,
+        ui: Option<&dyn UI>,
+        manager: Option<(&actors::popover::State, receiver::State)>,
         time: Timestamp,
-        button_pos: &ButtonPosition,
     ) {
-        let button = shape.get_button(button_pos).unwrap();
-        let action = button.action.clone();
-        match action {
-            Action::Submit {
-                text: Some(text),
-                keys: _,
-            } => submission.handle_press(
-                button_pos.into(),
-                SubmitData::Text(&text),
-                &button.keycodes,
-                time,
-            ),
-            Action::Submit {
-                text: None,
-                keys: _,
-            } => submission.handle_press(
-                button_pos.into(),
-                SubmitData::Keycodes,
-                &button.keycodes,
+        submission.record(recording::Entry::Release { time: time.0 });
+        if let Gesture::Glide(path) = &layout.state.gesture {
+            if !finish_glide(layout, submission, ui, path) {
+                // The held letter gets typed instead.
+                layout.state.gesture = Gesture::Idle;
+            }
+        }
+        // The list must be copied,
+        // because it will be mutated in the loop
+        let pressed_buttons = layout.state.active_buttons.clone();
+        for (button, _key_state) in pressed_buttons.iter_pressed() {
+            seat::handle_release_key(
+                layout,
+                submission,
+                ui,
+                time,
+                manager.as_ref()
+                    .map(|(popover, app_state)| (*popover, app_state.clone())),
+                button,
+            );
+        }
+        layout.state.gesture = Gesture::Idle;
+    }
+
+    /// Opens the layout chooser for a layout switching key
+    /// held long enough, without waiting for the release.
+    /// The key does nothing more after that.
+    /// Returns true if the chooser got opened.
+    pub fn open_held_chooser(
+        layout: &mut Layout,
+        submission: &mut Submission,
+        ui: Option<&dyn UI>,
+        manager: Option<(&actors::popover::State, receiver::State)>,
+        time: Timestamp,
+    ) -> bool {
+        let (ui, manager) = match (ui, manager) {
+            (Some(ui), Some(manager)) => (ui, manager),
+            _ => return false,
+        };
+        let held = layout.state.active_buttons.iter_pressed()
+            .map(|(button, _state)| button)
+            .find(|button| {
+                let switches_layout = match layout.shape.get_button(button) {
+                    Some(Button { action: Action::NextLayout, .. })
+                    | Some(Button { action: Action::PreviousLayout, .. }) => true,
+                    _ => false,
+                };
+                let long_press = layout.state.pressed_since.get(button)
+                    .map(|pressed| is_long_press(*pressed, time))
+                    .unwrap_or(false);
+                switches_layout && long_press
+            })
+            .cloned();
+        match held {
+            Some(button) => {
+                seat::show_popover(&layout.shape, Some(ui), Some(manager), &button);
+                seat::drop_key(layout, submission, time, &button);
+                // The finger is still down, but it's done with the keyboard.
+                layout.state.gesture = Gesture::Consumed;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Switches views directly, dropping any latch.
+    pub fn set_view(layout: &mut Layout, name: String) -> Result<(), NoSuchView> {
+        layout.set_view(name)?;
+        layout.state.view_latched = LatchedState::Not;
+        Ok(())
+    }
+
+    /// The distance moving the cursor by one character,
+    /// if the space bar is the only button held down.
+    fn get_trackpad_step(layout: &Layout) -> Option<f64> {
+        let space = layout.state.held_back.as_ref()?;
+        if layout.state.active_buttons.iter_pressed().count() != 1
+            || layout.shape.cursor_keys.is_none()
+            || !settings::get_space_trackpad()
+        {
+            return None;
+        }
+        layout.shape.get_button(space)
+            .filter(|button| button.action.is_space())
+            .map(|button| button.size.height / 2.0)
+    }
+
+    /// Checks if the finger holding a letter left it,
+    /// and went at least a key width away, to glide a word.
+    /// Swipes which may still hide the panel don't count.
+    fn starts_glide(
+        layout: &Layout,
+        submission: &Submission,
+        start: &c::Point,
+        current: &c::Point,
+    ) -> bool {
+        let letter = match &layout.state.held_back {
+            Some(letter) => letter,
+            None => return false,
+        };
+        if !settings::get_gesture_typing() || submission.is_private() {
+            return false;
+        }
+        if may_become_hide_swipe(start, current, settings::get_hide_swipe()) {
+            return false;
+        }
+        let (_keys, key_width) = layout.get_letter_keys();
+        let travelled = (current.x - start.x).hypot(current.y - start.y);
+        if travelled < key_width {
+            return false;
+        }
+        let is_letter = layout.shape.get_button(letter)
+            .and_then(|button| button.action.get_letter())
+            .is_some();
+        let current = layout.find_index_by_position(current.clone());
+        is_letter
+            && layout.state.active_buttons.iter_pressed().count() == 1
+            && current != Some((letter.row, letter.position_in_row))
+    }
+
+    /// How many words are offered besides the typed one
+    const ALTERNATIVES: usize = 4;
+
+    /// Types the word closest to the path, and offers the next best ones.
+    /// Returns false if no word got typed.
+    fn finish_glide(
+        layout: &Layout,
+        submission: &mut Submission,
+        ui: Option<&dyn UI>,
+        path: &[c::Point],
+    ) -> bool {
+        let dictionary = match glide::get_dictionary() {
+            Some(dictionary) => dictionary,
+            None => {
+                log_print!(logging::Level::Info, "Word list still loading");
+                return false;
+            },
+        };
+        let (keys, key_width) = layout.get_letter_keys();
+        let words = glide::decode(
+            path,
+            &keys,
+            key_width,
+            &dictionary,
+            ALTERNATIVES + 1,
+        );
+        // Gliding from a capital letter types a capitalized word.
+        let capital = layout.state.held_back.as_ref()
+            .and_then(|letter| layout.shape.get_button(letter))
+            .and_then(|button| button.action.get_letter())
+            .map(char::is_uppercase)
+            .unwrap_or(false);
+        let mut words = words.into_iter()
+            .map(|word| {
+                let word = match capital {
+                    true => {
+                        let mut letters = word.chars();
+                        letters.next()
+                            .map(|first| first.to_uppercase().chain(letters).collect())
+                            .unwrap_or(word)
+                    },
+                    false => word,
+                };
+                CString::new(format!("{} ", word))
+                    .expect("Word contains a NUL")
+            });
+
+        let typed = match words.next() {
+            Some(word) => word,
+            None => {
+                log_print!(logging::Level::Debug, "No word matches the glide");
+                return false;
+            },
+        };
+        match submission.type_word(&typed) {
+            Ok(()) => {
+                if let Some(ui) = ui {
+                    let (offset, view) = layout.get_current_view_position();
+                    let top_edge = c::Bounds {
+                        x: offset.x,
+                        y: offset.y,
+                        width: view.get_size().width,
+                        height: 0.0,
+                    };
+                    ui.show_alternatives(top_edge, typed, words.collect());
+                }
+                true
+            },
+            Err(e) => {
+                log_print!(
+                    logging::Level::Warning,
+                    "Can't type the glided word: {:?}", e,
+                );
+                false
+            },
+        }
+    }
+
+    /// Checks if the finger holding the space bar
+    /// moved sideways far enough, or stayed long enough,
+    /// to start moving the cursor.
+    fn starts_trackpad(
+        layout: &Layout,
+        start: &c::Point,
+        current: &c::Point,
+        time: Timestamp,
+    ) -> bool {
+        let step = match get_trackpad_step(layout) {
+            Some(step) => step,
+            None => return false,
+        };
+        let long_press = layout.state.held_back.as_ref()
+            .and_then(|space| layout.state.pressed_since.get(space))
+            .map(|pressed| is_long_press(*pressed, time))
+            .unwrap_or(false);
+        long_press || (current.x - start.x).abs() > step
+    }
+
+    /// Counts whole steps travelled in each direction,
+    /// positive towards the right and the bottom.
+    pub fn count_steps(last: &c::Point, current: &c::Point, step: f64) -> (i32, i32) {
+        let count = |distance: f64| (distance / step).trunc() as i32;
+        (count(current.x - last.x), count(current.y - last.y))
+    }
+
+    fn send_cursor_keys(
+        submission: &mut Submission,
+        keys: &CursorKeys,
+        (x, y): (i32, i32),
+        time: Timestamp,
+    ) {
+        let horizontal = if x < 0 { &keys.left } else { &keys.right };
+        let vertical = if y < 0 { &keys.up } else { &keys.down };
+        for _ in 0..x.abs() {
+            submission.tap_key(horizontal, time);
+        }
+        for _ in 0..y.abs() {
+            submission.tap_key(vertical, time);
+        }
+    }
+
+    /// Checks if the touch point travelled down far enough
+    /// to count as the swipe which hides the panel.
+    /// Mostly horizontal movements are left alone,
+    /// so that sliding along a row still works.
+    pub fn is_hide_swipe(
+        start: &c::Point,
+        current: &c::Point,
+        layout_height: f64,
+        config: settings::HideSwipe,
+    ) -> bool {
+        let down = current.y - start.y;
+        let sideways = (current.x - start.x).abs();
+        config.enabled
+            && down > config.distance * layout_height
+            && sideways < down / 2.0
+    }
+
+    /// Checks if the touch point is heading where the hide swipe goes,
+    /// but hasn't got far enough yet.
+    fn may_become_hide_swipe(
+        start: &c::Point,
+        current: &c::Point,
+        config: settings::HideSwipe,
+    ) -> bool {
+        let down = current.y - start.y;
+        let sideways = (current.x - start.x).abs();
+        config.enabled && down > 0.0 && sideways < down / 2.0
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        use crate::layout::test::*;
+        use crate::submission::memory;
+
+        const SWIPE: settings::HideSwipe = settings::HideSwipe {
+            enabled: true,
+            distance: 0.5,
+        };
+
+        /// Checks indexing of buttons
+        #[test]
+        fn view_has_button() {
+            let button = make_button("1".into());
+
+            let row = Row::new(vec!((0.1, button)));
+
+            let view = View::new(vec!((1.2, row)));
+
+            assert_eq!(
+                find_button_place(&view, (0, 0)),
+                Some((
+                    c::Point { x: 0.1, y: 1.2 },
+                    &make_button("1".into()),
+                ))
+            );
+
+            let view = View::new(vec![]);
+            assert_eq!(
+                find_button_place(&view, (0, 0)),
+                None,
+            );
+        }
+
+        #[test]
+        fn swipe_down_hides() {
+            assert!(is_hide_swipe(
+                &c::Point { x: 10.0, y: 0.0 },
+                &c::Point { x: 15.0, y: 60.0 },
+                100.0,
+                SWIPE,
+            ));
+        }
+
+        #[test]
+        fn swipe_short() {
+            assert!(!is_hide_swipe(
+                &c::Point { x: 10.0, y: 0.0 },
+                &c::Point { x: 10.0, y: 40.0 },
+                100.0,
+                SWIPE,
+            ));
+        }
+
+        #[test]
+        fn swipe_up_or_sideways() {
+            assert!(!is_hide_swipe(
+                &c::Point { x: 10.0, y: 60.0 },
+                &c::Point { x: 10.0, y: 0.0 },
+                100.0,
+                SWIPE,
+            ));
+            assert!(!is_hide_swipe(
+                &c::Point { x: 0.0, y: 0.0 },
+                &c::Point { x: 100.0, y: 60.0 },
+                100.0,
+                SWIPE,
+            ));
+        }
+
+        #[test]
+        fn swipe_disabled() {
+            assert!(!is_hide_swipe(
+                &c::Point { x: 10.0, y: 0.0 },
+                &c::Point { x: 10.0, y: 100.0 },
+                100.0,
+                settings::HideSwipe { enabled: false, ..SWIPE },
+            ));
+        }
+
+        #[test]
+        fn steps_round_towards_last() {
+            let last = c::Point { x: 10.0, y: 10.0 };
+            assert_eq!(
+                count_steps(&last, &c::Point { x: 12.5, y: 8.5 }, 1.0),
+                (2, -1),
+            );
+            assert_eq!(
+                count_steps(&last, &c::Point { x: 9.5, y: 10.9 }, 1.0),
+                (0, 0),
+            );
+        }
+
+        fn keycode(code: u32) -> KeyCode {
+            KeyCode { code, keymap_idx: 0 }
+        }
+
+        fn make_text_button(text: &str, width: f64, code: u32) -> Button {
+            Button {
+                size: Size { width, height: 2.0 },
+                action: Action::Submit {
+                    text: Some(CString::new(text).unwrap()),
+                    keys: Vec::new(),
+                },
+                keycodes: vec![keycode(code)],
+                ..make_button(text.into())
+            }
+        }
+
+        /// A wide space bar, stepping the cursor every 1.0
+        fn make_space_layout() -> Layout {
+            let space = make_text_button(" ", 10.0, 65);
+            make_layout(View::new(vec![(0.0, Row::new(vec![(0.0, space)]))]))
+        }
+
+        /// The letters t, g, b, one above the other
+        fn make_column_layout() -> Layout {
+            make_layout(View::new(vec![
+                (0.0, Row::new(vec![(0.0, make_text_button("t", 2.0, 28))])),
+                (2.0, Row::new(vec![(0.0, make_text_button("g", 2.0, 42))])),
+                (4.0, Row::new(vec![(0.0, make_text_button("b", 2.0, 56))])),
+            ]))
+        }
+
+        fn make_layout(view: View) -> Layout {
+            Layout::new(
+                LayoutParseData {
+                    views: hashmap! {
+                        "base".into() => (c::Point { x: 0.0, y: 0.0 }, view),
+                    },
+                    keymaps: vec![CString::new("keymap").unwrap()],
+                    margins: Margins {
+                        top: 0.0,
+                        left: 0.0,
+                        right: 0.0,
+                        bottom: 0.0,
+                    },
+                    cursor_keys: CursorKeys {
+                        left: keycode(113),
+                        right: keycode(114),
+                        up: keycode(111),
+                        down: keycode(116),
+                    },
+                },
+                ArrangementKind::Base,
+                ContentPurpose::Normal,
+            )
+        }
+
+        /// Presses at the first point, drags through the rest, releases,
+        /// and returns what got submitted.
+        fn touch(mut layout: Layout, points: &[(f64, f64, u32)]) -> Vec<memory::Event> {
+            let log = memory::new_log();
+            let mut submission = Submission::new(
+                Box::new(memory::VirtualKeyboard::new(log.clone())),
+                None,
+                None,
+            );
+            let point = |(x, y, _time): &(f64, f64, u32)| c::Point { x: *x, y: *y };
+
+            assert!(press(&mut layout, &mut submission, point(&points[0]), Timestamp(points[0].2)));
+            for p in &points[1..] {
+                drag(&mut layout, &mut submission, None, None, point(p), Timestamp(p.2));
+            }
+            release(&mut layout, &mut submission, None, None, Timestamp(1000));
+            let events = log.borrow().clone();
+            events
+        }
+
+        /// Returns the keycodes pressed.
+        fn touch_space(points: &[(f64, f64, u32)]) -> Vec<u32> {
+            touch(make_space_layout(), points).iter()
+                .filter_map(|event| match event {
+                    memory::Event::Key { code, pressed: true } => Some(*code),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// The letter t, and the digit 1 far to its right
+        fn make_glide_layout() -> Layout {
+            make_layout(View::new(vec![(0.0, Row::new(vec![
+                (0.0, make_text_button("t", 2.0, 28)),
+                (6.0, make_text_button("1", 2.0, 10)),
+            ]))]))
+        }
+
+        #[test]
+        fn glide_without_word() {
+            settings::GESTURE_TYPING.with(|enabled| enabled.set(true));
+            // No word ends far away from all letters.
+            let events = touch(
+                make_glide_layout(),
+                &[(1.0, 1.0, 0), (4.0, 1.1, 10), (7.0, 1.0, 20)],
+            );
+            settings::GESTURE_TYPING.with(|enabled| enabled.set(false));
+            let pressed: Vec<_> = events.iter()
+                .filter_map(|event| match event {
+                    memory::Event::Key { code, pressed: true } => Some(*code),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(pressed, vec![28]);
+        }
+
+        #[test]
+        fn hide_swipe_submits_nothing() {
+            let events = touch(
+                make_column_layout(),
+                &[(1.0, 1.0, 0), (1.1, 3.0, 10), (1.2, 5.0, 20)],
+            );
+            assert_eq!(events, Vec::new());
+        }
+
+        #[test]
+        fn slide_down_short() {
+            // Until the swipe is told apart, the first key stays pressed.
+            let events = touch(
+                make_column_layout(),
+                &[(1.0, 1.0, 0), (1.0, 2.5, 10)],
+            );
+            let pressed: Vec<_> = events.iter()
+                .filter_map(|event| match event {
+                    memory::Event::Key { code, pressed: true } => Some(*code),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(pressed, vec![28]);
+        }
+
+        #[test]
+        fn space_tap() {
+            assert_eq!(touch_space(&[(1.0, 1.0, 0)]), vec![65]);
+        }
+
+        #[test]
+        fn space_wobble() {
+            assert_eq!(touch_space(&[(1.0, 1.0, 0), (1.5, 1.2, 10)]), vec![65]);
+        }
+
+        #[test]
+        fn space_trackpad() {
+            assert_eq!(
+                touch_space(&[(1.0, 1.0, 0), (3.5, 1.0, 10), (3.5, -0.1, 20)]),
+                vec![114, 114, 111],
+            );
+        }
+
+        /// Space still held while the next letter goes down
+        #[test]
+        fn space_rollover() {
+            let mut layout = make_layout(View::new(vec![(0.0, Row::new(vec![
+                (0.0, make_text_button("a", 2.0, 38)),
+                (2.0, make_text_button(" ", 6.0, 65)),
+                (8.0, make_text_button("b", 2.0, 56)),
+            ]))]));
+            let log = memory::new_log();
+            let mut submission = Submission::new(
+                Box::new(memory::VirtualKeyboard::new(log.clone())),
+                None,
+                None,
+            );
+            for (x, time) in &[(1.0, 0), (5.0, 10), (9.0, 20)] {
+                let point = c::Point { x: *x, y: 1.0 };
+                assert!(press(&mut layout, &mut submission, point, Timestamp(*time)));
+            }
+            release(&mut layout, &mut submission, None, None, Timestamp(30));
+            let pressed: Vec<_> = log.borrow().iter()
+                .filter_map(|event| match event {
+                    memory::Event::Key { code, pressed: true } => Some(*code),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(pressed, vec![38, 65, 56]);
+        }
+
+        #[test]
+        fn space_trackpad_long_press() {
+            assert_eq!(
+                touch_space(&[
+                    (5.0, 1.0, 0),
+                    (4.5, 1.0, LONG_PRESS_MS),
+                    (3.9, 1.0, LONG_PRESS_MS + 10),
+                ]),
+                vec![113],
+            );
+        }
+    }
+}
+
+/// How long a button must be held to count as a long press, in milliseconds
+const LONG_PRESS_MS: u32 = 500;
+
+/// For the timer detecting long presses
+#[no_mangle]
+pub extern "C"
+fn squeek_layout_get_long_press_ms() -> u32 {
+    LONG_PRESS_MS
+}
+
+/// Timestamps are in milliseconds and may wrap around.
+fn is_long_press(pressed: Timestamp, released: Timestamp) -> bool {
+    released.0.wrapping_sub(pressed.0) >= LONG_PRESS_MS
+}
+
+/// What the layout needs from the user interface.
+/// Without one, the layout can still be used, e.g. for testing.
+pub trait UI {
+    /// Shows the layout chooser pointing at the area in layout coordinates
+    fn show_popover(
+        &self,
+        area: c::Bounds,
+        popover: &actors::popover::State,
+        app_state: receiver::State,
+    );
+    /// Shows the label enlarged above the area in layout coordinates
+    fn show_preview(&self, area: c::Bounds, label: &CStr);
+    fn hide_preview(&self);
+    /// Offers words to replace the `typed` one,
+    /// above the area in layout coordinates
+    fn show_alternatives(
+        &self,
+        area: c::Bounds,
+        typed: CString,
+        words: Vec<CString>,
+    );
+}
+
+pub struct UIBackend {
+    widget_to_layout: c::Transformation,
+    keyboard: c::EekGtkKeyboard,
+    /// For replacing words after the touch is over
+    submission: Rc<RefCell<Submission>>,
+}
+
+impl UI for UIBackend {
+    fn show_popover(
+        &self,
+        area: c::Bounds,
+        popover: &actors::popover::State,
+        app_state: receiver::State,
+    ) {
+        popover::show(
+            self.keyboard,
+            self.widget_to_layout.reverse_bounds(area),
+            popover,
+            app_state,
+        );
+    }
+
+    fn show_preview(&self, area: c::Bounds, label: &CStr) {
+        preview::show(
+            self.keyboard,
+            self.widget_to_layout.reverse_bounds(area),
+            label,
+        );
+    }
+
+    fn hide_preview(&self) {
+        preview::hide();
+    }
+
+    fn show_alternatives(
+        &self,
+        area: c::Bounds,
+        typed: CString,
+        words: Vec<CString>,
+    ) {
+        alternatives::show(
+            self.keyboard,
+            self.widget_to_layout.reverse_bounds(area),
+            self.submission.clone(),
+            typed,
+            words,
+        );
+    }
+}
+
+/// Top level procedures, dispatching to everything
+mod seat {
+    use super::*;
+
+    fn handle_press_key_cleaner(
+        shape: &LayoutData,
+        submission: &mut Submission,
+        time: Timestamp,
+        button_pos: &ButtonPosition,
+    ) {
+        let button = shape.get_button(button_pos).unwrap();
+        let action = button.action.clone();
+        match action {
+            Action::Submit {
+                text: Some(text),
+                keys: _,
+            } => submission.handle_press(
+                button_pos.into(),
+                SubmitData::Text(&text),
+                &button.keycodes,
+                time,
+            ),
+            Action::Submit {
+                text: None,
+                keys: _,
+            } => submission.handle_press(
+                button_pos.into(),
+                SubmitData::Keycodes,
+                &button.keycodes,
                 time,
             ),
             Action::Erase => submission.handle_press(
@@ -1113,8 +2140,32 @@ mod seat {
         time: Timestamp,
         button_pos: &ButtonPosition,
     ) {
+        let action = layout.shape.get_button(button_pos)
+            .map(|button| &button.action);
+        // Keys sending key codes can't wait, because holding them repeats.
+        let is_text = match action {
+            Some(Action::Submit { text: Some(_), .. }) => true,
+            _ => false,
+        };
+        let starts_gesture = match action {
+            Some(action) if action.is_space()
+                => layout.shape.cursor_keys.is_some()
+                    && settings::get_space_trackpad(),
+            Some(action) if action.get_letter().is_some()
+                => settings::get_gesture_typing() && !submission.is_private(),
+            _ => false,
+        };
+        // The hide swipe must not type anything either.
+        let holds_back = is_text
+            && (starts_gesture || settings::get_hide_swipe().enabled);
+
         // Send messages
-        handle_press_key_cleaner(&layout.shape, submission, time, button_pos);
+        if holds_back {
+            // Typed on release, unless it starts a gesture first
+            layout.state.held_back = Some(button_pos.clone());
+        } else {
+            handle_press_key_cleaner(&layout.shape, submission, time, button_pos);
+        }
     
         // Update state
         let find = layout.state.active_buttons.get(button_pos);
@@ -1128,13 +2179,74 @@ mod seat {
                 button_pos.clone(),
                 KeyState { pressed: PressType::Pressed },
             );
+            layout.state.pressed_since.insert(button_pos.clone(), time);
+        }
+    }
+
+    /// Types the held back key, unless a gesture took its place.
+    pub fn flush_held_back(
+        layout: &mut Layout,
+        submission: &mut Submission,
+        time: Timestamp,
+    ) {
+        let gestured = match layout.state.gesture {
+            Gesture::Trackpad { last: _, moved } => moved,
+            Gesture::Glide(_) => true,
+            _ => false,
+        };
+        if let Some(button_pos) = layout.state.held_back.take() {
+            if !gestured {
+                handle_press_key_cleaner(&layout.shape, submission, time, &button_pos);
+            }
+        }
+    }
+
+    /// Lets go of the key without submitting it or taking its action.
+    pub fn drop_key(
+        layout: &mut Layout,
+        submission: &mut Submission,
+        time: Timestamp,
+        button_pos: &ButtonPosition,
+    ) {
+        if layout.state.held_back.as_ref() == Some(button_pos) {
+            layout.state.held_back = None;
+        } else {
+            // Key codes already went out, and must not stay pressed.
+            submission.handle_release(button_pos.into(), time);
+        }
+        layout.state.pressed_since.remove(button_pos);
+        layout.state.active_buttons.remove(button_pos);
+    }
+
+    pub fn show_popover(
+        shape: &LayoutData,
+        ui: Option<&dyn UI>,
+        manager: Option<(&actors::popover::State, receiver::State)>,
+        button_pos: &ButtonPosition,
+    ) {
+        // only show when UI is present
+        if let Some(ui) = &ui {
+            // only show when layout manager is available
+            if let Some((manager, app_state)) = manager {
+                let place = shape.find_button_place(button_pos);
+
+                if let Some((position, button)) = place {
+                    let bounds = c::Bounds {
+                        x: position.x,
+                        y: position.y,
+                        width: button.size.width,
+                        height: button.size.height,
+                    };
+                    ui.show_popover(bounds, manager, app_state);
+                }
+            }
         }
     }
 
     fn handle_release_key_cleaner(
         shape: &LayoutData,
         submission: &mut Submission,
-        ui: Option<&UIBackend>,
+        ui: Option<&dyn UI>,
         time: Timestamp,
         // TODO: intermediate measure:
         // passing state conditionally because it's only used for popover.
@@ -1142,6 +2254,7 @@ mod seat {
         // and passed always.
         manager: Option<(&actors::popover::State, receiver::State)>,
         button_pos: &ButtonPosition,
+        long_press: bool,
     ) -> Action{
         let button = shape.get_button(&button_pos).unwrap();
         let action = button.action.clone();
@@ -1165,27 +2278,39 @@ mod seat {
                     false => submission.handle_drop_modifier(key_id, time),
                 }
             }
-            // only show when UI is present
-            Action::ShowPreferences => if let Some(ui) = &ui {
-                // only show when layout manager is available
-                if let Some((manager, app_state)) = manager {
-                    let place = shape.find_button_place(button_pos);
-
-                    if let Some((position, button)) = place {
-                        let bounds = c::Bounds {
-                            x: position.x,
-                            y: position.y,
-                            width: button.size.width,
-                            height: button.size.height,
-                        };
-                        popover::show(
-                            ui.keyboard,
-                            ui.widget_to_layout.reverse_bounds(bounds),
-                            manager,
-                            app_state,
-                        );
-                    }
-                }
+            Action::ShowPreferences => show_popover(shape, ui, manager, button_pos),
+            Action::NextLayout | Action::PreviousLayout if long_press
+                => show_popover(shape, ui, manager, button_pos),
+            Action::NextLayout => if let Some((manager, app_state)) = manager {
+                popover::cycle_layout(manager, app_state, popover::Direction::Next);
+            },
+            Action::PreviousLayout => if let Some((manager, app_state)) = manager {
+                popover::cycle_layout(manager, app_state, popover::Direction::Previous);
+            },
+            Action::Hide => if let Some((_, app_state)) = manager {
+                app_state
+                    .send(state::Event::Visibility(
+                        state::visibility::Event::ForceHidden,
+                    ))
+                    .or_print(
+                        logging::Problem::Warning,
+                        "Can't send to state manager",
+                    );
+            },
+            // Only react to real touches, not to simulated ones.
+            Action::ToggleOneHanded => if ui.is_some() {
+                let config = settings::get_one_handed();
+                settings::set_one_handed(settings::OneHanded {
+                    enabled: !config.enabled,
+                    ..config
+                });
+            },
+            Action::FlipOneHanded => if ui.is_some() {
+                let config = settings::get_one_handed();
+                settings::set_one_handed(settings::OneHanded {
+                    side: config.side.opposite(),
+                    ..config
+                });
             },
             // Other keys are handled in view switcher before.
             _ => {}
@@ -1201,7 +2326,7 @@ mod seat {
     pub fn handle_release_key(
         layout: &mut Layout,
         submission: &mut Submission,
-        ui: Option<&UIBackend>,
+        ui: Option<&dyn UI>,
         time: Timestamp,
         // TODO: intermediate measure:
         // passing state conditionally because it's only used for popover.
@@ -1210,6 +2335,14 @@ mod seat {
         manager: Option<(&actors::popover::State, receiver::State)>,
         button_pos: &ButtonPosition,
     ) {
+        let long_press = layout.state.pressed_since.remove(button_pos)
+            .map(|pressed| is_long_press(pressed, time))
+            .unwrap_or(false);
+
+        if layout.state.held_back.as_ref() == Some(button_pos) {
+            flush_held_back(layout, submission, time);
+        }
+
         // Send events
         let action = handle_release_key_cleaner(
             &layout.shape,
@@ -1218,6 +2351,7 @@ mod seat {
             time,
             manager,
             button_pos,
+            long_press,
         );
         
         // Apply state changes
@@ -1245,7 +2379,9 @@ mod test {
             name: CString::new(name.clone()).unwrap(),
             size: Size { width: 0f64, height: 0f64 },
             outline_name: CString::new("test").unwrap(),
+            classes: Vec::new(),
             label: Label::Text(CString::new(name).unwrap()),
+            hint: None,
             action: Action::SetView("default".into()),
             keycodes: Vec::new(),
         }
@@ -1317,6 +2453,9 @@ mod test {
                 current_view: "base".into(),
                 view_latched: LatchedState::Not,
                 active_buttons: ActiveButtons(HashMap::new()),
+                pressed_since: HashMap::new(),
+                gesture: Gesture::Idle,
+                held_back: None,
             },
             shape: LayoutData {
                 keymaps: Vec::new(),
@@ -1335,6 +2474,7 @@ mod test {
                     "locked".into() => (c::Point { x: 0.0, y: 0.0 }, view),
                 },
                 purpose: ContentPurpose::Normal,
+                cursor_keys: None,
             },
         };
 
@@ -1354,6 +2494,118 @@ mod test {
         assert_eq!(&layout.state.current_view, "base");
     }
 
+    #[test]
+    fn preview_single_press() {
+        let button = Button {
+            size: Size { width: 2.0, height: 3.0 },
+            ..make_button("a".into())
+        };
+        let view = View::new(vec![(
+            1.0,
+            Row::new(vec![
+                (0.0, button.clone()),
+                (2.0, Button {
+                    label: Label::IconName(CString::new("icon").unwrap()),
+                    ..button
+                }),
+            ]),
+        )]);
+        let mut layout = Layout {
+            state: LayoutState {
+                current_view: "base".into(),
+                view_latched: LatchedState::Not,
+                active_buttons: ActiveButtons(HashMap::new()),
+                pressed_since: HashMap::new(),
+                gesture: Gesture::Idle,
+                held_back: None,
+            },
+            shape: LayoutData {
+                keymaps: Vec::new(),
+                kind: ArrangementKind::Base,
+                margins: Margins {
+                    top: 0.0,
+                    left: 0.0,
+                    right: 0.0,
+                    bottom: 0.0,
+                },
+                views: hashmap! {
+                    "base".into() => (c::Point { x: 10.0, y: 0.0 }, view),
+                },
+                purpose: ContentPurpose::Normal,
+                cursor_keys: None,
+            },
+        };
+        let position = |position_in_row| ButtonPosition {
+            view: "base".into(),
+            row: 0,
+            position_in_row,
+        };
+        let pressed = KeyState { pressed: PressType::Pressed };
+
+        assert_eq!(layout.find_preview(), None);
+
+        layout.state.active_buttons.insert(position(0), pressed.clone());
+        assert_eq!(
+            layout.find_preview(),
+            Some((
+                c::Bounds { x: 10.0, y: 1.0, width: 2.0, height: 3.0 },
+                CString::new("a").unwrap(),
+            )),
+        );
+
+        layout.state.active_buttons.insert(position(1), pressed.clone());
+        assert_eq!(layout.find_preview(), None);
+
+        layout.state.active_buttons.remove(&position(0));
+        assert_eq!(layout.find_preview(), None, "Icons are not previewed");
+    }
+
+    #[test]
+    fn letter_keys() {
+        let letter = |text: &str| Button {
+            size: Size { width: 2.0, height: 4.0 },
+            action: Action::Submit {
+                text: Some(CString::new(text).unwrap()),
+                keys: Vec::new(),
+            },
+            ..make_button(text.into())
+        };
+        let view = View::new(vec![(
+            0.0,
+            Row::new(vec![
+                (0.0, letter("Q")),
+                (2.0, letter(",")),
+                (4.0, letter("ab")),
+            ]),
+        )]);
+        let layout = Layout::new(
+            LayoutParseData {
+                views: hashmap! {
+                    "base".into() => (c::Point { x: 1.0, y: 0.0 }, view),
+                },
+                keymaps: Vec::new(),
+                margins: Margins {
+                    top: 0.0,
+                    left: 0.0,
+                    right: 0.0,
+                    bottom: 0.0,
+                },
+                cursor_keys: CursorKeys {
+                    left: KeyCode { code: 9, keymap_idx: 0 },
+                    right: KeyCode { code: 10, keymap_idx: 0 },
+                    up: KeyCode { code: 11, keymap_idx: 0 },
+                    down: KeyCode { code: 12, keymap_idx: 0 },
+                },
+            },
+            ArrangementKind::Base,
+            ContentPurpose::Normal,
+        );
+        assert_eq!(
+            layout.get_letter_keys(),
+            (vec![('q', c::Point { x: 2.0, y: 2.0 })], 2.0),
+        );
+    }
+
     #[test]
     fn reverse_unlatch_layout() {
         let switch = Action::LockView {
@@ -1397,6 +2649,9 @@ mod test {
                 current_view: "base".into(),
                 view_latched: LatchedState::Not,
                 active_buttons: ActiveButtons(HashMap::new()),
+                pressed_since: HashMap::new(),
+                gesture: Gesture::Idle,
+                held_back: None,
             },
             shape: LayoutData {
                 keymaps: Vec::new(),
@@ -1416,6 +2671,7 @@ mod test {
                     "unlocked".into() => (c::Point { x: 0.0, y: 0.0 }, view),
                 },
                 purpose: ContentPurpose::Normal,
+                cursor_keys: None,
             },
         };
 
@@ -1468,6 +2724,9 @@ mod test {
                 current_view: "base".into(),
                 view_latched: LatchedState::Not,
                 active_buttons: ActiveButtons(HashMap::new()),
+                pressed_since: HashMap::new(),
+                gesture: Gesture::Idle,
+                held_back: None,
             },
             shape: LayoutData {
                 keymaps: Vec::new(),
@@ -1487,6 +2746,7 @@ mod test {
                     "ĄĘ".into() => (c::Point { x: 0.0, y: 0.0 }, view),
                 },
                 purpose: ContentPurpose::Normal,
+                cursor_keys: None,
             },
         };
 
@@ -1586,6 +2846,7 @@ mod test {
                 String::new() => (c::Point { x: 0.0, y: 0.0 }, view),
             },
             purpose: ContentPurpose::Normal,
+            cursor_keys: None,
         };
         assert_eq!(
             layout.calculate_inner_size(),
@@ -1635,6 +2896,7 @@ mod test {
                 String::new() => (c::Point { x: 0.0, y: 0.0 }, view),
             },
             purpose: ContentPurpose::Normal,
+            cursor_keys: None,
         };
         let transformation = layout.calculate_transformation(
             Size { width: 100.0, height: 100.0 }
@@ -1657,4 +2919,71 @@ mod test {
         assert_eq!(transformation.scale_x, 100.0);
         assert_eq!(transformation.scale_y, 100.0);
     }
+
+    #[test]
+    fn check_one_handed() {
+        // just one button
+        let view = View::new(vec![
+            (
+                0.0,
+                Row::new(vec![(
+                    0.0,
+                    Button {
+                        size: Size { width: 1.0, height: 1.0 },
+                        ..make_button("foo".into())
+                    },
+                )]),
+            ),
+        ]);
+        let layout = LayoutData {
+            keymaps: Vec::new(),
+            kind: ArrangementKind::Base,
+            margins: Margins {
+                top: 0.0,
+                left: 0.0,
+                right: 0.0,
+                bottom: 0.0,
+            },
+            views: hashmap! {
+                String::new() => (c::Point { x: 0.0, y: 0.0 }, view),
+            },
+            purpose: ContentPurpose::Normal,
+            cursor_keys: None,
+        };
+        let available = Size { width: 100.0, height: 100.0 };
+        let config = settings::OneHanded {
+            enabled: true,
+            side: settings::Side::Right,
+            width: 0.5,
+        };
+        let transformation = layout.calculate_one_handed_transformation(
+            available.clone(),
+            &config,
+        );
+        assert_eq!(transformation.origin_x, 50.0);
+        assert_eq!(transformation.origin_y, 25.0);
+        assert_eq!(transformation.scale_x, 50.0);
+        assert_eq!(transformation.scale_y, 50.0);
+
+        let transformation = layout.calculate_one_handed_transformation(
+            available.clone(),
+            &settings::OneHanded { side: settings::Side::Left, ..config },
+        );
+        assert_eq!(transformation.origin_x, 0.0);
+        assert_eq!(transformation.scale_x, 50.0);
+
+        let transformation = layout.calculate_one_handed_transformation(
+            available,
+            &settings::OneHanded { enabled: false, ..config },
+        );
+        assert_eq!(transformation.origin_x, 0.0);
+        assert_eq!(transformation.scale_x, 100.0);
+    }
+
+    #[test]
+    fn long_press_wraps() {
+        assert!(!is_long_press(Timestamp(1000), Timestamp(1100)));
+        assert!(is_long_press(Timestamp(1000), Timestamp(1000 + LONG_PRESS_MS)));
+        assert!(is_long_press(Timestamp(u32::MAX - 100), Timestamp(LONG_PRESS_MS)));
+    }
 }
@@ -12,6 +12,7 @@ extern crate gtk_sys;
 #[macro_use] // only for tests
 extern crate maplit;
 extern crate serde;
+extern crate serde_json;
 extern crate xkbcommon;
 extern crate zbus;
 extern crate zvariant;
@@ -24,11 +25,13 @@ mod logging;
 
 mod action;
 pub mod actors;
+mod alternatives;
 mod animation;
 pub mod data;
 mod drawing;
 mod event_loop;
 pub mod float_ord;
+mod glide;
 pub mod imservice;
 mod keyboard;
 mod layout;
@@ -37,8 +40,12 @@ mod main;
 mod outputs;
 mod panel;
 mod popover;
+mod preview;
 mod receiver;
+mod recording;
 pub mod resources;
+mod settings;
+pub mod simulator;
 mod state;
 mod style;
 mod submission;
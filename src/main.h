@@ -27,12 +27,15 @@ struct rsobjects {
     struct squeek_popover *popover;
 };
 
-void register_ui_loop_handler(struct receiver *receiver, struct panel_manager *panel, struct squeek_popover *popover, EekboardContextService *hint_manager, DBusHandler *dbus_handler);
+void register_ui_loop_handler(struct receiver *receiver, struct panel_manager *panel, struct squeek_popover *popover, EekboardContextService *hint_manager, DBusHandler *dbus_handler, struct squeek_state_manager *state_manager);
 
 struct rsobjects squeek_init(void);
 
 void squeek_state_send_force_visible(struct squeek_state_manager *state);
 void squeek_state_send_force_hidden(struct squeek_state_manager *state);
+void squeek_state_send_overlay_set(struct squeek_state_manager *state, const char *name);
+void squeek_state_send_view_set(struct squeek_state_manager *state, const char *name);
+void squeek_state_send_focused_output(struct squeek_state_manager *state, const char *name);
 
-void squeek_state_send_keyboard_present(struct squeek_state_manager *state, uint32_t keyboard_present);
+void squeek_state_send_enabled(struct squeek_state_manager *state, uint32_t enabled);
 void squeek_state_send_layout_set(struct squeek_state_manager *state, char *name, char *layout, uint32_t timestamp);
@@ -5,11 +5,16 @@
 /*! Glue for the main loop. */
 use crate::actors;
 use crate::actors::external::debug;
+use crate::actors::external::keyboards;
 use crate::animation;
 use crate::data::loading;
 use crate::event_loop;
+use crate::glide;
 use crate::panel;
+use crate::recording;
+use crate::settings;
 use crate::state;
+use crate::style;
 use glib::{Continue, MainContext, PRIORITY_DEFAULT, Receiver};
 
 
@@ -24,13 +29,14 @@ mod c {
     use crate::actors::Destination;
     use crate::actors::popover;
     use crate::event_loop::driver;
-    use crate::imservice::IMService;
+    use crate::imservice::{ IMService, InputMethodSink };
     use crate::imservice::c::InputMethod;
     use crate::layout;
     use crate::outputs::Outputs;
     use crate::state;
     use crate::submission::Submission;
     use crate::util::c::{ArcWrapped, Wrapped};
+    use crate::vkeyboard::VirtualKeyboard;
     use crate::vkeyboard::c::ZwpVirtualKeyboardV1;
     
     /// DbusHandler*
@@ -66,6 +72,7 @@ mod c {
         input_method_manager: *const c_void,
         outputs: Wrapped<Outputs>,
         seat: *const c_void,
+        fractional_scale_manager: *const c_void,
         input_method: InputMethod,
         virtual_keyboard: ZwpVirtualKeyboardV1,
     }
@@ -78,6 +85,7 @@ mod c {
                 input_method_manager: ptr::null(),
                 outputs: Wrapped::new(outputs_manager),
                 seat: ptr::null(),
+                fractional_scale_manager: ptr::null(),
                 input_method: InputMethod::null(),
                 virtual_keyboard: ZwpVirtualKeyboardV1::null(),
             }
@@ -92,6 +100,10 @@ mod c {
         // This should probably only get called from the gtk main loop,
         // given that dbus handler is using glib.
         fn dbus_handler_set_visible(dbus: *const DBusHandler, visible: u8);
+        fn dbus_handler_set_layout(dbus: *const DBusHandler, layout: *const c_char, overlay: *const c_char);
+        fn dbus_handler_set_height(dbus: *const DBusHandler, height: u32);
+        fn dbus_handler_set_view(dbus: *const DBusHandler, view: *const c_char);
+        fn eekboard_context_service_set_view(service: HintManager, view: *const c_char) -> u8;
     }
     
     // INITIALIZATION
@@ -109,6 +121,70 @@ mod c {
 
         debug::init(state_manager.clone());
 
+        state_manager
+            .send(state::Event::PanelHeight(settings::get_panel_height()))
+            .or_print(logging::Problem::Warning, "Can't send to state manager");
+        {
+            let sender = state_manager.clone();
+            settings::connect_panel_height(move |height| {
+                sender.send(state::Event::PanelHeight(height))
+                    .or_print(logging::Problem::Warning, "Can't send to state manager");
+            });
+        }
+
+        state_manager
+            .send(state::Event::OutputPolicy(settings::get_output_policy()))
+            .or_print(logging::Problem::Warning, "Can't send to state manager");
+        {
+            let sender = state_manager.clone();
+            settings::connect_output_policy(move |policy| {
+                sender.send(state::Event::OutputPolicy(policy))
+                    .or_print(logging::Problem::Warning, "Can't send to state manager");
+            });
+        }
+
+        state_manager
+            .send(state::Event::SlideDuration(settings::get_slide_duration()))
+            .or_print(logging::Problem::Warning, "Can't send to state manager");
+        {
+            let sender = state_manager.clone();
+            settings::connect_slide_duration(move |duration| {
+                sender.send(state::Event::SlideDuration(duration))
+                    .or_print(logging::Problem::Warning, "Can't send to state manager");
+            });
+        }
+
+        state_manager
+            .send(state::Event::Floating(settings::get_floating()))
+            .or_print(logging::Problem::Warning, "Can't send to state manager");
+        {
+            let sender = state_manager.clone();
+            settings::connect_floating(move |floating| {
+                sender.send(state::Event::Floating(floating))
+                    .or_print(logging::Problem::Warning, "Can't send to state manager");
+            });
+        }
+
+        state_manager
+            .send(state::Event::KeyboardPolicy(settings::get_keyboard_policy()))
+            .or_print(logging::Problem::Warning, "Can't send to state manager");
+        {
+            let sender = state_manager.clone();
+            settings::connect_keyboard_policy(move |policy| {
+                sender.send(state::Event::KeyboardPolicy(policy))
+                    .or_print(logging::Problem::Warning, "Can't send to state manager");
+            });
+        }
+        keyboards::init(state_manager.clone());
+
+        // Otherwise it starts loading on the first glide.
+        if settings::get_gesture_typing() {
+            glide::preload();
+        }
+
+        style::init_user_style();
+        style::init_accessibility();
+
         let outputs = Outputs::new(state_manager.clone());
         let mut wayland = Box::new(Wayland::new(outputs));
         let wayland_raw = &mut *wayland as *mut _;
@@ -119,14 +195,26 @@ mod c {
         let imservice = if wayland.input_method.is_null() {
             None
         } else {
-            Some(IMService::new(wayland.input_method, state_manager.clone()))
+            let imservice = IMService::new(
+                wayland.input_method,
+                state_manager.clone(),
+            );
+            Some(imservice as Box<dyn InputMethodSink>)
         };
-        let submission = Submission::new(vk, imservice);
+        let recorder = recording::Recorder::from_env();
+        recording::set_global(recorder.clone());
+        let submission = Submission::new(
+            Box::new(VirtualKeyboard::new(vk)),
+            imservice,
+            recorder,
+        );
         
         let popover = ArcWrapped::new(actors::popover::State::new(true));
 
         #[cfg(feature = "zbus_v1_5")]
         crate::actors::external::screensaver::init(popover.clone_ref());
+        #[cfg(feature = "zbus_v1_5")]
+        crate::actors::external::color_scheme::init();
         
         RsObjects {
             submission: Wrapped::new(submission),
@@ -146,11 +234,15 @@ mod c {
         popover: actors::popover::c::Actor,
         hint_manager: HintManager,
         dbus_handler: *const DBusHandler,
+        state_manager: Wrapped<EventLoop>,
     ) {
         let receiver = unsafe { receiver.unwrap() };
         let receiver = Rc::try_unwrap(receiver).expect("References still present");
         let receiver = receiver.into_inner();
-        let panel_manager = Wrapped::new(panel::Manager::new(panel_manager));
+        let state_manager = state_manager.clone_ref().borrow().clone();
+        let panel_manager = Wrapped::new(
+            panel::Manager::new(panel_manager, state_manager.clone())
+        );
         let ctx = MainContext::default();
         let _acqu = ctx.acquire();
         receiver.attach(
@@ -162,6 +254,7 @@ mod c {
                     &popover.clone_ref(),
                     hint_manager,
                     dbus_handler,
+                    &state_manager,
                 );
                 Continue(true)
             },
@@ -180,13 +273,27 @@ mod c {
         popover: &actors::popover::Destination,
         hint_manager: HintManager,
         dbus_handler: *const DBusHandler,
+        state_manager: &EventLoop,
     ) {
+        if let Some(offset) = msg.panel_offset {
+            panel::Manager::set_offset(panel_manager.clone(), offset);
+        }
+
         if let Some(visibility) = msg.panel_visibility {
+            if !dbus_handler.is_null() {
+                let height = match &visibility {
+                    panel::Command::Show { height, .. } => height.as_scaled_ceiling(),
+                    // Applications don't need to make room for it.
+                    panel::Command::ShowFloating { .. } => 0,
+                    panel::Command::Hide => 0,
+                };
+                unsafe { dbus_handler_set_height(dbus_handler, height) };
+            }
             panel::Manager::update(panel_manager, visibility);
         }
 
         if let Some(visible) = msg.dbus_visible_set {
-            if dbus_handler != std::ptr::null() {
+            if !dbus_handler.is_null() {
                 unsafe { dbus_handler_set_visible(dbus_handler, visible as u8) };
             }
         }
@@ -199,6 +306,27 @@ mod c {
                 purpose,
             } = description;
             popover.send(popover::Event::Overlay(overlay_name.clone()));
+            if !dbus_handler.is_null() {
+                let to_cstring = |s: &str| CString::new(s)
+                    .unwrap_or(CString::new("").unwrap());
+                let layout_name = to_cstring(&name);
+                let overlay = to_cstring(overlay_name.as_deref().unwrap_or(""));
+                unsafe {
+                    dbus_handler_set_layout(
+                        dbus_handler,
+                        layout_name.as_ptr(),
+                        overlay.as_ptr(),
+                    )
+                };
+            }
+            recording::with_global(|recorder| recorder.record(
+                recording::Entry::Layout {
+                    name: name.clone(),
+                    kind: kind as u32,
+                    purpose: purpose as u32,
+                    overlay: overlay_name.clone(),
+                }
+            ));
             let layout = loading::load_layout(&name, kind, purpose, &overlay_name);
             let layout = Box::into_raw(Box::new(layout));
             // CSS can't express "+" in the class
@@ -212,12 +340,32 @@ mod c {
                 eekboard_context_service_set_layout(hint_manager, name, layout, 0);
             }
         }
+
+        // After the layout, which would otherwise reset the view.
+        if let Some(state::ViewChoice { name, serial }) = msg.view_selection {
+            let view = CString::new(name).unwrap_or(CString::new("").unwrap());
+            let applied = unsafe {
+                eekboard_context_service_set_view(hint_manager, view.as_ptr())
+            };
+            if applied == 0 {
+                log_print!(
+                    logging::Level::Warning,
+                    "Requested view {:?} is not in the layout", view,
+                );
+            } else if !dbus_handler.is_null() {
+                unsafe { dbus_handler_set_view(dbus_handler, view.as_ptr()) };
+            }
+            // Once shown, the view is up to the user again.
+            state_manager.send(state::Event::ViewApplied(serial))
+                .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
+        }
     }
     
     // EVENT PASSING    
 
     use crate::logging;
-    use crate::state::{Event, Presence};
+    use crate::popover::LayoutId;
+    use crate::state::Event;
     use crate::state::LayoutChoice;
     use crate::state::visibility;
     use crate::util;
@@ -244,13 +392,51 @@ mod c {
 
     #[no_mangle]
     pub extern "C"
-    fn squeek_state_send_keyboard_present(sender: Wrapped<EventLoop>, present: u32) {
+    fn squeek_state_send_overlay_set(sender: Wrapped<EventLoop>, name: *const c_char) {
+        let sender = sender.clone_ref();
+        let sender = sender.borrow();
+        let name = String::from(
+            util::c::as_str(&name)
+            .unwrap_or(Some(""))
+            .unwrap_or("")
+        );
+        sender.send(Event::OverlayChanged(LayoutId::Local(name)))
+            .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
+    }
+
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_state_send_view_set(sender: Wrapped<EventLoop>, name: *const c_char) {
+        let sender = sender.clone_ref();
+        let sender = sender.borrow();
+        let name = String::from(
+            util::c::as_str(&name)
+            .unwrap_or(Some(""))
+            .unwrap_or("")
+        );
+        sender.send(Event::ViewChoice(name))
+            .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
+    }
+
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_state_send_focused_output(sender: Wrapped<EventLoop>, name: *const c_char) {
+        let sender = sender.clone_ref();
+        let sender = sender.borrow();
+        let name = util::c::as_str(&name)
+            .unwrap_or(None)
+            .filter(|name| !name.is_empty())
+            .map(String::from);
+        sender.send(Event::FocusedOutput(name))
+            .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
+    }
+
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_state_send_enabled(sender: Wrapped<EventLoop>, enabled: u32) {
         let sender = sender.clone_ref();
         let sender = sender.borrow();
-        let state =
-            if present == 0 { Presence::Missing }
-            else { Presence::Present };
-        sender.send(Event::PhysicalKeyboard(state))
+        sender.send(Event::ScreenKeyboardEnabled(enabled != 0))
             .or_warn(&mut logging::Print, logging::Problem::Warning, "Can't send to state manager");
     }
     
@@ -295,7 +481,11 @@ pub mod commands {
 /// to be sent out to external components.
 #[derive(Clone)]
 pub struct Commands {
+    /// Applied before visibility, so that a new panel starts in place
+    pub panel_offset: Option<panel::PixelSize>,
     pub panel_visibility: Option<panel::Command>,
     pub dbus_visible_set: Option<bool>,
     pub layout_selection: Option<commands::SetLayout>,
+    /// Applied after the layout
+    pub view_selection: Option<state::ViewChoice>,
 }
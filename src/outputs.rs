@@ -33,6 +33,15 @@ pub mod c {
         fn null() -> Self {
             Self(ptr::null())
         }
+
+        /// Addresses this low are never mapped, so they can't belong to real objects.
+        pub(super) fn synthetic(index: u16) -> Self {
+            Self(index as usize as *const c_void)
+        }
+
+        pub(super) fn is_synthetic(&self) -> bool {
+            !self.0.is_null() && (self.0 as usize) <= u16::MAX as usize
+        }
     }
 
     #[repr(C)]
@@ -101,7 +110,7 @@ pub mod c {
     }
     
     impl Transform {
-        fn from_u32(v: u32) -> Option<Transform> {
+        pub fn from_u32(v: u32) -> Option<Transform> {
             use self::Transform::*;
             match v {
                 0 => Some(Normal),
@@ -461,15 +470,41 @@ impl OutputState {
         }
     }
 
+    /// Wayland doesn't tell which outputs have touch,
+    /// but built-in screens usually do,
+    /// and they can be told apart by the connector name.
+    pub fn is_builtin(&self) -> bool {
+        const BUILTIN_CONNECTORS: &[&str] = &["DSI-", "eDP-", "LVDS-"];
+        match &self.name {
+            Some(name) => BUILTIN_CONNECTORS.iter()
+                .any(|prefix| name.starts_with(prefix)),
+            None => false,
+        }
+    }
+
     /// Return physical dimensions adjusted for current transform
     pub fn get_physical_size(&self) -> Option<Size<Option<Millimeter>>> {
-        match self {
+        let size = match self {
             OutputState {
                 geometry: Some(Geometry { transform, phys_size } ),
                 ..
-            } => Some(Self::transform_size(phys_size.width, phys_size.height, *transform)),
-            _ => None,
-        }
+            } => Self::transform_size(phys_size.width, phys_size.height, *transform),
+            _ => return None,
+        };
+        // Some compositors report the physical size already transformed.
+        // Then it ends up in a different orientation than the pixels.
+        let swapped = match (self.get_pixel_size(), size.width, size.height) {
+            (Some(pixels), Some(width), Some(height)) => {
+                pixels.width != pixels.height
+                    && width.0 != height.0
+                    && (pixels.width > pixels.height) != (width.0 > height.0)
+            },
+            _ => false,
+        };
+        Some(match swapped {
+            true => Size { width: size.height, height: size.width },
+            false => size,
+        })
     }
 }
 
@@ -483,6 +518,19 @@ pub struct OutputId(pub c::WlOutput);
 // we're only using it as a lookup key.
 unsafe impl Send for OutputId {}
 
+impl OutputId {
+    /// Stands for an output the compositor doesn't know about,
+    /// for debugging.
+    pub fn synthetic(index: u16) -> Self {
+        OutputId(c::WlOutput::synthetic(index))
+    }
+
+    /// Such outputs must never be handed over to Wayland.
+    pub fn is_synthetic(&self) -> bool {
+        self.0.is_synthetic()
+    }
+}
+
 struct Output {
     output: c::WlOutput,
     pending: OutputState,
@@ -1,3 +1,5 @@
+#include <gdk/gdkwayland.h>
+
 #include "eekboard/eekboard-context-service.h"
 #include "wayland.h"
 #include "panel.h"
@@ -21,11 +23,13 @@ on_destroy (struct panel_manager *self, GtkWidget *widget)
 }
 
 
-/// panel::Manager. Only needed for this callback
-struct squeek_panel_manager;
+/// Height of the handle for dragging the floating panel
+#define HANDLE_HEIGHT 16
 
 /// Calls back into Rust
 void squeek_panel_manager_configured(struct squeek_panel_manager *mgr, uint32_t width, uint32_t height);
+void squeek_panel_manager_moved(struct squeek_panel_manager *mgr, int32_t x, int32_t y);
+void squeek_panel_manager_scaled(struct squeek_panel_manager *mgr, uint32_t scale);
 
 static void
 on_surface_configure(struct squeek_panel_manager *self, PhoshLayerSurface *surface)
@@ -41,6 +45,102 @@ on_surface_configure(struct squeek_panel_manager *self, PhoshLayerSurface *surfa
     squeek_panel_manager_configured(self, width, height);
 }
 
+/// The scale only goes into sizing the panel.
+/// Drawing still happens at the integer scale GTK picks,
+/// because GTK 3 can't draw into a buffer of fractional scale,
+/// so the compositor still resamples the panel on fractionally scaled outputs.
+static void
+on_preferred_scale (void *data, struct wp_fractional_scale_v1 *fractional_scale, uint32_t scale)
+{
+    (void)fractional_scale;
+    struct panel_manager *self = data;
+    squeek_panel_manager_scaled(self->mgr, scale);
+}
+
+static const struct wp_fractional_scale_v1_listener fractional_scale_listener = {
+    .preferred_scale = on_preferred_scale,
+};
+
+/// The wl_surface only exists while the window is mapped.
+static void
+on_map (struct panel_manager *self, GtkWidget *widget)
+{
+    (void)widget;
+    if (!squeek_wayland->fractional_scale_manager || self->fractional_scale) {
+        return;
+    }
+    self->fractional_scale = wp_fractional_scale_manager_v1_get_fractional_scale(
+        squeek_wayland->fractional_scale_manager,
+        phosh_layer_surface_get_wl_surface(self->window)
+    );
+    wp_fractional_scale_v1_add_listener(self->fractional_scale, &fractional_scale_listener, self);
+}
+
+static void
+on_unmap (struct panel_manager *self, GtkWidget *widget)
+{
+    (void)widget;
+    if (self->fractional_scale) {
+        wp_fractional_scale_v1_destroy(self->fractional_scale);
+        self->fractional_scale = NULL;
+    }
+}
+
+/// The bottom margin also carries the slide offset.
+/// Negative margins put the panel partly below the edge of the output.
+static void
+apply_margins (struct panel_manager *self)
+{
+    if (self->floating) {
+        phosh_layer_surface_set_margins(self->window, 0, 0, self->floating_y - (int32_t)self->offset, self->floating_x);
+    } else {
+        phosh_layer_surface_set_margins(self->window, 0, 0, -(int32_t)self->offset, 0);
+    }
+}
+
+/// Keeps the whole floating panel on its output.
+static void
+clamp_position (struct panel_manager *self)
+{
+    int32_t max_x = G_MAXINT32;
+    int32_t max_y = G_MAXINT32;
+    GdkDisplay *display = gdk_display_get_default();
+    for (int i = 0; i < gdk_display_get_n_monitors(display); i++) {
+        GdkMonitor *monitor = gdk_display_get_monitor(display, i);
+        if (gdk_wayland_monitor_get_wl_output(monitor) == self->current_output) {
+            GdkRectangle area;
+            gdk_monitor_get_geometry(monitor, &area);
+            max_x = area.width - gtk_widget_get_allocated_width(GTK_WIDGET(self->window));
+            max_y = area.height - gtk_widget_get_allocated_height(GTK_WIDGET(self->window));
+        }
+    }
+    self->floating_x = CLAMP(self->floating_x, 0, MAX(0, max_x));
+    self->floating_y = CLAMP(self->floating_y, 0, MAX(0, max_y));
+}
+
+static void
+on_handle_drag_update (struct panel_manager *self, gdouble offset_x, gdouble offset_y, GtkGestureDrag *gesture)
+{
+    (void)gesture;
+    // The handle moves together with the surface,
+    // so the offset is how far the finger moved since the previous update.
+    self->floating_x += (int32_t)offset_x;
+    self->floating_y -= (int32_t)offset_y;
+    clamp_position(self);
+    apply_margins(self);
+    phosh_layer_surface_wl_surface_commit(self->window);
+}
+
+/// The last update already moved the panel.
+static void
+on_handle_drag_end (struct panel_manager *self, gdouble offset_x, gdouble offset_y, GtkGestureDrag *gesture)
+{
+    (void)offset_x;
+    (void)offset_y;
+    (void)gesture;
+    squeek_panel_manager_moved(self->mgr, self->floating_x, self->floating_y);
+}
+
 static void
 make_widget (struct panel_manager *self)
 {
@@ -48,10 +148,29 @@ make_widget (struct panel_manager *self)
         g_error("Widget already present");
     }
     self->widget = eek_gtk_keyboard_new (self->state, self->submission, self->state_manager, self->popover);
-
     gtk_widget_set_has_tooltip (self->widget, TRUE);
-    gtk_container_add (GTK_CONTAINER(self->window), self->widget);
-    gtk_widget_show_all(self->widget);
+
+    self->handle = gtk_event_box_new();
+    gtk_widget_set_size_request(self->handle, -1, HANDLE_HEIGHT);
+    gtk_style_context_add_class(gtk_widget_get_style_context(self->handle), "handle");
+    GtkWidget *grip = gtk_separator_new(GTK_ORIENTATION_HORIZONTAL);
+    gtk_widget_set_valign(grip, GTK_ALIGN_CENTER);
+    gtk_widget_set_halign(grip, GTK_ALIGN_CENTER);
+    gtk_widget_set_size_request(grip, 4 * HANDLE_HEIGHT, -1);
+    gtk_container_add(GTK_CONTAINER(self->handle), grip);
+
+    self->handle_drag = gtk_gesture_drag_new(self->handle);
+    g_object_connect (self->handle_drag,
+        "swapped-signal::drag-update", G_CALLBACK(on_handle_drag_update), self,
+        "swapped-signal::drag-end", G_CALLBACK(on_handle_drag_end), self,
+        NULL);
+
+    GtkWidget *box = gtk_box_new(GTK_ORIENTATION_VERTICAL, 0);
+    gtk_box_pack_start(GTK_BOX(box), self->handle, FALSE, FALSE, 0);
+    gtk_box_pack_start(GTK_BOX(box), self->widget, TRUE, TRUE, 0);
+    gtk_container_add (GTK_CONTAINER(self->window), box);
+    gtk_widget_show_all(box);
+    gtk_widget_set_visible(self->handle, self->floating);
 }
 
 
@@ -65,41 +184,115 @@ panel_manager_resize (struct panel_manager *self, uint32_t height)
     phosh_layer_surface_wl_surface_commit(self->window);
 }
 
+// Called from rust
+/// Moves the floating panel
+void
+panel_manager_move (struct panel_manager *self, int32_t x, int32_t y)
+{
+    self->floating_x = x;
+    self->floating_y = y;
+    apply_margins(self);
+    phosh_layer_surface_wl_surface_commit(self->window);
+}
+
+// Called from rust
+/// Slides the panel down by the offset, for animations.
+/// Remembered for the next time the panel is shown.
+void
+panel_manager_set_offset (struct panel_manager *self, uint32_t offset)
+{
+    self->offset = offset;
+    if (self->window) {
+        apply_margins(self);
+        phosh_layer_surface_wl_surface_commit(self->window);
+    }
+}
+
+static void
+make_window (struct panel_manager *self, struct wl_output *output, uint32_t height, struct squeek_panel_manager *mgr)
+{
+    self->window = g_object_new (
+        PHOSH_TYPE_LAYER_SURFACE,
+        "layer-shell", squeek_wayland->layer_shell,
+        "wl-output", output,
+        "height", height,
+        "anchor", ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM
+        | ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT
+        | ZWLR_LAYER_SURFACE_V1_ANCHOR_RIGHT,
+        "layer", ZWLR_LAYER_SHELL_V1_LAYER_TOP,
+        "kbd-interactivity", FALSE,
+        "exclusive-zone", height,
+        "namespace", "osk",
+        NULL
+    );
+    self->mgr = mgr;
+    g_object_connect (self->window,
+        "swapped-signal::destroy", G_CALLBACK(on_destroy), self,
+        "swapped-signal::configured", G_CALLBACK(on_surface_configure), mgr,
+        "swapped-signal-after::map", G_CALLBACK(on_map), self,
+        "swapped-signal::unmap", G_CALLBACK(on_unmap), self,
+        NULL);
+    // The properties below are just to make hacking easier.
+    // The way we use layer-shell overrides some,
+    // and there's no space in the protocol for others.
+    // Those may still be useful in the future,
+    // or for hacks with regular windows.
+    gtk_widget_set_can_focus (GTK_WIDGET(self->window), FALSE);
+    g_object_set (G_OBJECT(self->window), "accept_focus", FALSE, NULL);
+    gtk_window_set_title (GTK_WINDOW(self->window), "Squeekboard");
+    gtk_window_set_icon_name (GTK_WINDOW(self->window), "squeekboard");
+    gtk_window_set_keep_above (GTK_WINDOW(self->window), TRUE);
+}
+
+// Called from rust
+/// Shows the panel above applications.
+/// If the panel was docked, it must be hidden first,
+/// because anchors only apply to a fresh surface.
+void
+panel_manager_request_floating (struct panel_manager *self, struct wl_output *output, uint32_t width, uint32_t height, int32_t x, int32_t y, struct squeek_panel_manager *mgr)
+{
+    self->floating = TRUE;
+    self->current_output = output;
+    if (!self->window) {
+        make_window(self, output, height, mgr);
+    }
+    g_object_set (G_OBJECT(self->window),
+        "wl-output", output,
+        "anchor", ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM
+        | ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT,
+        "exclusive-zone", 0,
+        NULL);
+    phosh_layer_surface_set_size(self->window, width, height + HANDLE_HEIGHT);
+    panel_manager_move(self, x, y);
+
+    if (!self->widget) {
+        make_widget(self);
+    }
+    gtk_widget_set_visible(self->handle, TRUE);
+
+    gtk_widget_show (GTK_WIDGET(self->window));
+}
+
 // Called from rust
 /// Creates a new panel widget
 void
 panel_manager_request_widget (struct panel_manager *self, struct wl_output *output, uint32_t height, struct squeek_panel_manager *mgr)
 {
     if (!self->window) {
-        self->window = g_object_new (
-            PHOSH_TYPE_LAYER_SURFACE,
-            "layer-shell", squeek_wayland->layer_shell,
-            "wl-output", output,
-            "height", height,
-            "anchor", ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM
-            | ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT
-            | ZWLR_LAYER_SURFACE_V1_ANCHOR_RIGHT,
-            "layer", ZWLR_LAYER_SHELL_V1_LAYER_TOP,
-            "kbd-interactivity", FALSE,
-            "exclusive-zone", height,
-            "namespace", "osk",
-            NULL
-        );
-        g_object_connect (self->window,
-            "swapped-signal::destroy", G_CALLBACK(on_destroy), self,
-            "swapped-signal::configured", G_CALLBACK(on_surface_configure), mgr,
-            NULL);
-        // The properties below are just to make hacking easier.
-        // The way we use layer-shell overrides some,
-        // and there's no space in the protocol for others.
-        // Those may still be useful in the future,
-        // or for hacks with regular windows.
-        gtk_widget_set_can_focus (GTK_WIDGET(self->window), FALSE);
-        g_object_set (G_OBJECT(self->window), "accept_focus", FALSE, NULL);
-        gtk_window_set_title (GTK_WINDOW(self->window), "Squeekboard");
-        gtk_window_set_icon_name (GTK_WINDOW(self->window), "squeekboard");
-        gtk_window_set_keep_above (GTK_WINDOW(self->window), TRUE);
+        make_window(self, output, height, mgr);
+        apply_margins(self);
     } else {
+        if (self->floating) {
+            // Coming back from floating. The surface was already hidden.
+            self->floating = FALSE;
+            g_object_set (G_OBJECT(self->window),
+                "anchor", ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM
+                | ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT
+                | ZWLR_LAYER_SURFACE_V1_ANCHOR_RIGHT,
+                NULL);
+            gtk_widget_set_visible(self->handle, FALSE);
+        }
+        apply_margins(self);
         // FIXME: Make use of mgr->current_output to track this
         g_object_set (G_OBJECT(self->window), "wl-output", output, NULL);
         panel_manager_resize(self, height);
@@ -119,6 +312,14 @@ struct panel_manager panel_manager_new(EekboardContextService *state, struct sub
         .submission = submission,
         .window = NULL,
         .widget = NULL,
+        .handle = NULL,
+        .handle_drag = NULL,
+        .floating = FALSE,
+        .floating_x = 0,
+        .floating_y = 0,
+        .mgr = NULL,
+        .fractional_scale = NULL,
+        .offset = 0,
         .current_output = NULL,
         .state_manager = state_manager,
         .popover = popover,
@@ -5,6 +5,9 @@
 #include "src/main.h"
 #include "src/submission.h"
 
+/// panel::Manager
+struct squeek_panel_manager;
+
 // Stores the objects that the panel and its widget will refer to
 struct panel_manager {
     EekboardContextService *state; // unowned
@@ -17,6 +20,20 @@ struct panel_manager {
     // https://gitlab.gnome.org/World/Phosh/squeekboard/-/issues/343
     PhoshLayerSurface *window;
     GtkWidget *widget;
+    /// For dragging the floating panel around
+    GtkWidget *handle;
+    GtkGesture *handle_drag;
+
+    /// Whether the panel floats above applications
+    gboolean floating;
+    /// Position of the floating panel, see panel::Position
+    int32_t floating_x, floating_y;
+    /// panel::Manager, for reporting the new position and scale
+    struct squeek_panel_manager *mgr;
+    /// How far the panel slid towards the bottom edge
+    uint32_t offset;
+    /// Exists while the window is mapped, if the compositor supports it
+    struct wp_fractional_scale_v1 *fractional_scale;
 
     // Those should be held in Rust
     struct wl_output *current_output;
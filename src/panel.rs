@@ -26,12 +26,21 @@
  *
  * Tight coupling, e.g. a future one between presented hints and layout size,
  * will have to be taken into account later.
+ *
+ * The allocated size is still reported to `state::State` after each configure,
+ * so that the arrangement can follow the width the panel actually got.
  */
 
 use crate::logging;
+use crate::main;
 use crate::outputs::OutputId;
+use crate::settings;
+use crate::state;
 use crate::util::c::Wrapped;
 
+// Traits
+use crate::logging::Warn;
+
 
 pub mod c {
     use super::*;
@@ -54,6 +63,19 @@ pub mod c {
             // for callbacks
             panel: Wrapped<Manager>,
         );
+        #[allow(improper_ctypes)]
+        pub fn panel_manager_request_floating(
+            service: PanelManager,
+            output: WlOutput,
+            width: u32,
+            height: u32,
+            x: i32,
+            y: i32,
+            // for callbacks
+            panel: Wrapped<Manager>,
+        );
+        pub fn panel_manager_move(service: PanelManager, x: i32, y: i32);
+        pub fn panel_manager_set_offset(service: PanelManager, offset: u32);
         pub fn panel_manager_resize(service: PanelManager, height: u32);
         pub fn panel_manager_hide(service: PanelManager);
     }
@@ -69,35 +91,82 @@ pub mod c {
             glib::Continue(false)
         });
     }
+
+    /// The floating panel was dragged to a new place
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_panel_manager_moved(panel: Wrapped<Manager>, x: i32, y: i32) {
+        let panel = panel.clone_ref();
+        panel.borrow_mut().set_moved(Position { x, y });
+    }
+
+    /// The compositor prefers a new scale for the panel.
+    /// In 120ths.
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_panel_manager_scaled(panel: Wrapped<Manager>, scale: u32) {
+        let panel = panel.clone_ref();
+        panel.borrow().set_scale(Scale(scale));
+    }
 }
 
 
+/// Scale factor in 120ths, the way wp-fractional-scale-v1 reports it.
+/// Without that protocol, only integer scales from wl_output are known.
+/// It's used for sizing only: the panel is still drawn at an integer scale.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scale(pub u32);
+
+impl Scale {
+    pub fn from_integer(scale: u32) -> Self {
+        Scale(scale * 120)
+    }
+
+    /// Converts scaled pixels to real pixels
+    pub fn apply(&self, scaled: u32) -> u32 {
+        (scaled as u64 * self.0 as u64 / 120) as u32
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.0 as f64 / 120.0
+    }
+}
+
 /// Size in pixels that is aware of scaling
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct PixelSize {
     pub pixels: u32,
-    pub scale_factor: u32,
+    pub scale_factor: Scale,
 }
 
-fn div_ceil(a: u32, b: u32) -> u32 {
+fn div_ceil(a: u64, b: u64) -> u64 {
     // Given that it's for pixels on a screen, an overflow is unlikely.
     (a + b - 1) / b
 }
 
 impl PixelSize {
     pub fn as_scaled_floor(&self) -> u32 {
-        self.pixels / self.scale_factor
+        (self.pixels as u64 * 120 / self.scale_factor.0 as u64) as u32
     }
 
     pub fn as_scaled_ceiling(&self) -> u32 {
-        div_ceil(self.pixels, self.scale_factor)
+        div_ceil(self.pixels as u64 * 120, self.scale_factor.0 as u64) as u32
     }
 }
 
-#[derive(Clone, Debug)]
-struct Size {
-    width: u32,
-    height: u32,
+/// In scaled pixels, as given by the compositor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Distance of the panel's bottom left corner
+/// from the output's bottom left corner, in scaled pixels
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
 }
 
 /// This state requests the Wayland layer shell protocol synchronization:
@@ -117,6 +186,12 @@ enum State {
         wanted_height: u32,
         allocated: Size,
     },
+    /// Not anchored to the edges, so the compositor doesn't get a say in the size.
+    Floating {
+        output: OutputId,
+        size: Size,
+        position: Position,
+    },
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -125,6 +200,13 @@ pub enum Command {
         output: OutputId,
         height: PixelSize,
     },
+    /// Show above applications, without reserving space
+    ShowFloating {
+        output: OutputId,
+        width: PixelSize,
+        height: PixelSize,
+        position: Position,
+    },
     Hide,
 }
 
@@ -136,18 +218,23 @@ pub struct Manager {
     // It's here because State doesn't need to become more complex
     // until this becomes properly used.
     debug: bool,
+    /// Receives the allocated size, to adjust the layout to it
+    state_manager: main::EventLoop,
+    /// Slide offset in scaled pixels
+    offset: u32,
 }
 
 impl Manager {
-    pub fn new(panel: c::PanelManager) -> Self {
+    pub fn new(panel: c::PanelManager, state_manager: main::EventLoop) -> Self {
         Self {
             panel,
             state: State::Hidden,
             debug: false,
+            state_manager,
+            offset: 0,
         }
     }
-    // TODO: mabe send the allocated size back to state::State,
-    // to perform layout adjustments
+
     fn set_configured(&mut self, size: Size) {
         if self.debug {
             eprintln!("Panel received configure {:?}", &size);
@@ -174,13 +261,67 @@ impl Manager {
                 wanted_height: height,
                 allocated: size,
             },
+            floating @ State::Floating{..} => floating,
         };
 
+        if let State::SizeAllocated{output, allocated, ..} = &self.state {
+            self.state_manager
+                .send(state::Event::PanelConfigured {
+                    output: *output,
+                    size: *allocated,
+                })
+                .or_print(logging::Problem::Warning, "Can't send to state manager");
+        }
+
         if self.debug {
             eprintln!("Panel now {:?}", &self.state);
         }
     }
 
+    fn set_moved(&mut self, new_position: Position) {
+        if let State::Floating { position, .. } = &mut self.state {
+            *position = new_position;
+            settings::set_floating_position(new_position.x, new_position.y);
+        } else {
+            log_print!(
+                logging::Level::Surprise,
+                "Panel moved, but it's not floating. Ignoring",
+            );
+        }
+    }
+
+    fn set_scale(&self, scale: Scale) {
+        let output = match &self.state {
+            State::Hidden => None,
+            State::SizeRequested{output, ..}
+            | State::SizeAllocated{output, ..}
+            | State::Floating{output, ..} => Some(*output),
+        };
+        match output {
+            Some(output) => {
+                self.state_manager
+                    .send(state::Event::PanelScale { output, scale })
+                    .or_print(logging::Problem::Warning, "Can't send to state manager");
+            },
+            None => log_print!(
+                logging::Level::Surprise,
+                "Panel got a scale, but it's hidden. Ignoring",
+            ),
+        }
+    }
+
+    /// Slides the panel towards the bottom edge.
+    /// Also applies to a panel shown later.
+    pub fn set_offset(mgr: Wrapped<Manager>, offset: PixelSize) {
+        let mgr = mgr.clone_ref();
+        let mut mgr = mgr.borrow_mut();
+        let offset = offset.as_scaled_ceiling();
+        if offset != mgr.offset {
+            mgr.offset = offset;
+            unsafe { c::panel_manager_set_offset(mgr.panel, offset); }
+        }
+    }
+
     pub fn update(mgr: Wrapped<Manager>, cmd: Command) {
         let copied = mgr.clone();
 
@@ -191,8 +332,76 @@ impl Manager {
             eprintln!("Panel received {:?}", &cmd);
         }
 
+        let cmd = match cmd {
+            Command::Show{output, ..} | Command::ShowFloating{output, ..}
+                if output.is_synthetic()
+            => {
+                log_print!(
+                    logging::Level::Info,
+                    "Not showing the panel on fake output {:?}", output,
+                );
+                Command::Hide
+            },
+            cmd => cmd,
+        };
+
         (*mgr).state = match (cmd, mgr.state.clone()) {
             (Command::Hide, State::Hidden) => State::Hidden,
+            (Command::Hide, State::Floating{..}) => {
+                unsafe { c::panel_manager_hide(mgr.panel); }
+                State::Hidden
+            },
+            (
+                Command::ShowFloating{output, width, height, position},
+                State::Floating{output: old_output, size: old_size, position: old_position},
+            ) => {
+                let size = Size {
+                    width: width.as_scaled_ceiling(),
+                    height: height.as_scaled_ceiling(),
+                };
+                if output == old_output && size == old_size {
+                    if position != old_position {
+                        unsafe { c::panel_manager_move(mgr.panel, position.x, position.y); }
+                    }
+                } else {
+                    unsafe {
+                        c::panel_manager_request_floating(
+                            mgr.panel, output.0,
+                            size.width, size.height,
+                            position.x, position.y,
+                            copied,
+                        );
+                    }
+                }
+                State::Floating{output, size, position}
+            },
+            (Command::ShowFloating{output, width, height, position}, old_state) => {
+                if let State::Hidden = old_state {} else {
+                    // The anchors change, so the surface must be recreated.
+                    unsafe { c::panel_manager_hide(mgr.panel); }
+                }
+                let size = Size {
+                    width: width.as_scaled_ceiling(),
+                    height: height.as_scaled_ceiling(),
+                };
+                unsafe {
+                    c::panel_manager_request_floating(
+                        mgr.panel, output.0,
+                        size.width, size.height,
+                        position.x, position.y,
+                        copied,
+                    );
+                }
+                State::Floating{output, size, position}
+            },
+            (Command::Show{output, height}, State::Floating{..}) => {
+                let height = height.as_scaled_ceiling();
+                unsafe {
+                    c::panel_manager_hide(mgr.panel);
+                    c::panel_manager_request_widget(mgr.panel, output.0, height, copied);
+                }
+                State::SizeRequested{output, height}
+            },
             (Command::Hide, State::SizeAllocated{..}) => {
                 unsafe { c::panel_manager_hide(mgr.panel); }
                 State::Hidden
@@ -1,5 +1,10 @@
 #pragma once
+#include <glib.h>
+
 /// Popover state.
 /// Wrapped<actors::popover::State>
 struct squeek_popover;
 
+/// Returns a new a(ss) variant of (type, name) pairs
+GVariant *squeek_list_layouts(void);
+
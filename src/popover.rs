@@ -10,6 +10,8 @@ use crate::locale::{ OwnedTranslation, compare_current_locale };
 use crate::logging;
 use crate::receiver;
 use crate::resources;
+use crate::settings;
+use crate::settings::get_settings;
 use crate::state;
 
 // Traits
@@ -21,11 +23,28 @@ use gtk::prelude::*;
 use crate::logging::Warn;
 
 mod c {
+    use super::*;
+
     use std::os::raw::c_char;
 
+    use glib::translate::ToGlibPtr;
+
     extern "C" {
         pub fn popover_open_settings_panel(panel: *const c_char);
     }
+
+    /// Returns a new reference to an a(ss) variant
+    #[no_mangle]
+    pub extern "C"
+    fn squeek_list_layouts() -> *mut glib_sys::GVariant {
+        let layouts = list_layouts().into_iter()
+            .map(|layout| match layout {
+                LayoutId::System { kind, name } => (kind, name),
+                LayoutId::Local(name) => ("overlay".into(), name),
+            })
+            .collect();
+        variants::ArrayPairString(layouts).to_variant().to_glib_full()
+    }
 }
 
 mod variants {
@@ -105,30 +124,6 @@ mod variants {
     }
 }
 
-fn get_settings(schema_name: &str) -> Option<gio::Settings> {
-    let mut error_handler = logging::Print{};
-
-    #[cfg(feature = "glib_v0_14")]
-    let ss = gio::SettingsSchemaSource::default();
-    #[cfg(not(feature = "glib_v0_14"))]
-    let ss = gio::SettingsSchemaSource::get_default();
-    
-    ss.or_warn(
-            &mut error_handler,
-            logging::Problem::Surprise,
-            "No gsettings schemas installed.",
-        )
-        .and_then(|sss| 
-            sss.lookup(schema_name, true)
-                .or_warn(
-                    &mut error_handler,
-                    logging::Problem::Surprise,
-                    &format!("Gsettings schema {} not installed", schema_name),
-                )
-        )
-        .map(|_sschema| gio::Settings::new(schema_name))
-}
-
 fn set_layout(kind: &str, name: &str) {
     let settings = get_settings("org.gnome.desktop.input-sources");
     if let Some(settings) = settings {
@@ -231,18 +226,9 @@ fn translate_layout_names(layouts: &Vec<LayoutId>) -> Vec<OwnedTranslation> {
         .collect()
 }
 
-pub fn show(
-    window: EekGtkKeyboard,
-    position: Bounds,
-    popover: &actors::popover::State,
-    app_state: receiver::State,
-) {
-    unsafe { gtk::set_initialized() };
-    let window = unsafe { gtk::Widget::from_glib_none(window.0) };
-
-    let overlay_layouts = resources::get_overlays().into_iter()
-        .map(|name| LayoutId::Local(name.to_string()));
-
+/// System layouts, in the order of the settings.
+/// The first one is the one in use.
+fn get_system_layouts() -> Vec<LayoutId> {
     let settings = get_settings("org.gnome.desktop.input-sources");
     let inputs = settings
         .map(|settings| {
@@ -254,19 +240,29 @@ pub fn show(
             variants::get_tuples(inputs)
         })
         .unwrap_or_else(|| Vec::new());
-    
-    let system_layouts: Vec<LayoutId> = inputs.into_iter()
+
+    inputs.into_iter()
         .map(|(kind, name)| LayoutId::System { kind, name })
-        .collect();
+        .collect()
+}
 
-    let all_layouts: Vec<LayoutId> = system_layouts.clone()
-        .into_iter()
-        .chain(overlay_layouts)
-        .collect();
+/// All choices: system layouts in the order of the settings,
+/// followed by overlays
+fn list_layouts() -> Vec<LayoutId> {
+    get_system_layouts().into_iter()
+        .chain(
+            resources::get_overlays().into_iter()
+                .map(|name| LayoutId::Local(name.to_string()))
+        )
+        .collect()
+}
 
-    let translated_names = translate_layout_names(&system_layouts);
+/// Sorted collection of language layouts, in the order presented to the user
+fn get_human_names(system_layouts: &Vec<LayoutId>)
+    -> Vec<(OwnedTranslation, LayoutId)>
+{
+    let translated_names = translate_layout_names(system_layouts);
 
-    // sorted collection of language layouts
     let mut human_names: Vec<(OwnedTranslation, LayoutId)> = translated_names
         .into_iter()
         .zip(system_layouts.clone().into_iter())
@@ -280,6 +276,103 @@ pub fn show(
             _ => compare_current_locale(&tr_a.0, &tr_b.0)
         }
     });
+    human_names
+}
+
+/// Makes the layout the one presented,
+/// both in the app state and in the system settings.
+fn select_layout(layout: &LayoutId, app_state: &receiver::State) {
+    app_state
+        .send(state::Event::OverlayChanged(layout.clone()))
+        .or_print(
+            logging::Problem::Bug,
+            &format!("Can't send to state"),
+        );
+    set_visible_layout(layout)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Next,
+    Previous,
+}
+
+/// Finds the layout next to the current one, wrapping around.
+/// Layouts are compared by name, like in the menu.
+fn get_neighbor<'a>(
+    choices: &'a [LayoutId],
+    current: Option<&LayoutId>,
+    direction: Direction,
+) -> Option<&'a LayoutId> {
+    let count = choices.len();
+    if count == 0 {
+        return None;
+    }
+    let index = current.and_then(|current|
+        choices.iter()
+            .position(|l| l.get_name() == current.get_name())
+    );
+    let index = match (index, direction) {
+        (None, _) => 0,
+        (Some(i), Direction::Next) => (i + 1) % count,
+        (Some(i), Direction::Previous) => (i + count - 1) % count,
+    };
+    choices.get(index)
+}
+
+/// Switches to the next layout, in the order of the popover menu,
+/// without showing the menu.
+pub fn cycle_layout(
+    popover: &actors::popover::State,
+    app_state: receiver::State,
+    direction: Direction,
+) {
+    let system_layouts = get_system_layouts();
+    // The order of system layouts in the settings changes
+    // every time one gets selected,
+    // so follow the sorted menu order instead to visit them all.
+    let choices: Vec<LayoutId> = get_human_names(&system_layouts)
+        .into_iter()
+        .map(|(_tr, layout)| layout)
+        .chain(
+            resources::get_overlays().into_iter()
+                .map(|name| LayoutId::Local(name.to_string()))
+        )
+        .collect();
+
+    let current = get_current_layout(popover, &system_layouts);
+    match get_neighbor(&choices, current.as_ref(), direction) {
+        Some(layout) => {
+            log_print!(logging::Level::Debug, "Cycling to layout {:?}", layout);
+            select_layout(layout, &app_state);
+        },
+        None => log_print!(
+            logging::Level::Surprise,
+            "No layouts to cycle through",
+        ),
+    }
+}
+
+pub fn show(
+    window: EekGtkKeyboard,
+    position: Bounds,
+    popover: &actors::popover::State,
+    app_state: receiver::State,
+) {
+    unsafe { gtk::set_initialized() };
+    let window = unsafe { gtk::Widget::from_glib_none(window.0) };
+
+    let overlay_layouts = resources::get_overlays().into_iter()
+        .map(|name| LayoutId::Local(name.to_string()));
+
+    let system_layouts = get_system_layouts();
+
+    let all_layouts: Vec<LayoutId> = system_layouts.clone()
+        .into_iter()
+        .chain(overlay_layouts)
+        .collect();
+
+    let human_names = get_human_names(&system_layouts);
 
     let model: gio::Menu = {
         #[cfg(feature = "glib_v0_14")]
@@ -344,13 +437,7 @@ pub fn show(
                                 .find(
                                     |choices| state == choices.get_name()
                                 ).unwrap();
-                            app_state
-                                .send(state::Event::OverlayChanged(layout.clone()))
-                                .or_print(
-                                    logging::Problem::Bug,
-                                    &format!("Can't send to state"),
-                                );
-                            set_visible_layout(layout)
+                            select_layout(layout, &app_state)
                         });
                 },
                 None => log_print!(
@@ -371,6 +458,37 @@ pub fn show(
     });
     action_group.add_action(&settings_action);
 
+    // Not every layout has buttons for the one-handed mode.
+    let one_handed = settings::get_one_handed();
+    let one_handed_action = gio::SimpleAction::new_stateful(
+        "one-handed",
+        None,
+        &one_handed.enabled.to_variant(),
+    );
+    one_handed_action.connect_change_state(|action, state| {
+        if let Some(state) = state {
+            if let Some(enabled) = state.get::<bool>() {
+                settings::set_one_handed(settings::OneHanded {
+                    enabled,
+                    ..settings::get_one_handed()
+                });
+                action.set_state(state);
+            }
+        }
+    });
+    action_group.add_action(&one_handed_action);
+
+    let flip_action = gio::SimpleAction::new("one-handed-flip", None);
+    flip_action.set_enabled(one_handed.enabled);
+    flip_action.connect_activate(|_, _| {
+        let config = settings::get_one_handed();
+        settings::set_one_handed(settings::OneHanded {
+            side: config.side.opposite(),
+            ..config
+        });
+    });
+    action_group.add_action(&flip_action);
+
     menu.insert_action_group("popup", Some(&action_group));
 
     menu.bind_model(Some(&model), Some("popup"));
@@ -379,3 +497,52 @@ pub fn show(
         Continue(false)
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn choices() -> Vec<LayoutId> {
+        vec![
+            LayoutId::System { kind: "xkb".into(), name: "de".into() },
+            LayoutId::System { kind: "xkb".into(), name: "us".into() },
+            LayoutId::Local("emoji".into()),
+        ]
+    }
+
+    #[test]
+    fn cycle_next() {
+        let choices = choices();
+        assert_eq!(
+            get_neighbor(&choices, Some(&choices[0]), Direction::Next),
+            Some(&choices[1]),
+        );
+    }
+
+    #[test]
+    fn cycle_wraps() {
+        let choices = choices();
+        assert_eq!(
+            get_neighbor(&choices, Some(&choices[2]), Direction::Next),
+            Some(&choices[0]),
+        );
+        assert_eq!(
+            get_neighbor(&choices, Some(&choices[0]), Direction::Previous),
+            Some(&choices[2]),
+        );
+    }
+
+    #[test]
+    fn cycle_unknown_current() {
+        let choices = choices();
+        assert_eq!(
+            get_neighbor(
+                &choices,
+                Some(&LayoutId::Local("terminal".into())),
+                Direction::Next,
+            ),
+            Some(&choices[0]),
+        );
+        assert_eq!(get_neighbor(&[], None, Direction::Next), None);
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/*! The bubble showing the pressed key above the finger */
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use crate::layout::c::{ Bounds, EekGtkKeyboard };
+
+// Traits
+use glib::translate::FromGlibPtrNone;
+use gtk::prelude::*;
+
+
+struct Preview {
+    popover: gtk::Popover,
+    label: gtk::Label,
+}
+
+thread_local! {
+    /// Reused between presses, to avoid flicker while typing fast.
+    static PREVIEW: RefCell<Option<Preview>> = const { RefCell::new(None) };
+}
+
+fn create(window: &gtk::Widget) -> Preview {
+    let popover = gtk::Popover::new(Some(window));
+    // Taking the focus or the input would break typing.
+    popover.set_modal(false);
+    popover.set_can_focus(false);
+    popover.set_position(gtk::PositionType::Top);
+    popover.set_constrain_to(gtk::PopoverConstraint::None);
+    #[cfg(feature = "glib_v0_14")]
+    popover.style_context().add_class("sq_preview");
+    #[cfg(not(feature = "glib_v0_14"))]
+    popover.get_style_context().add_class("sq_preview");
+
+    let label = gtk::Label::new(None);
+    label.show();
+    popover.add(&label);
+    Preview { popover, label }
+}
+
+/// Shows the label above the position, given in widget coordinates.
+pub fn show(window: EekGtkKeyboard, position: Bounds, label: &CStr) {
+    unsafe { gtk::set_initialized() };
+    let window = unsafe { gtk::Widget::from_glib_none(window.0) };
+    let label = label.to_string_lossy();
+    PREVIEW.with(|preview| {
+        let mut preview = preview.borrow_mut();
+
+        #[cfg(feature = "glib_v0_14")]
+        let stale = preview.as_ref()
+            .map(|p| p.popover.relative_to().as_ref() != Some(&window));
+        #[cfg(not(feature = "glib_v0_14"))]
+        let stale = preview.as_ref()
+            .map(|p| p.popover.get_relative_to().as_ref() != Some(&window));
+
+        // The keyboard widget gets recreated with the panel.
+        if stale.unwrap_or(true) {
+            if let Some(old) = preview.take() {
+                unsafe { old.popover.destroy() };
+            }
+            *preview = Some(create(&window));
+        }
+        let preview = preview.as_ref().unwrap();
+
+        preview.label.set_markup(&format!(
+            "<span size=\"xx-large\">{}</span>",
+            glib::markup_escape_text(&label),
+        ));
+        preview.popover.set_pointing_to(&gtk::Rectangle {
+            x: position.x.ceil() as i32,
+            y: position.y.ceil() as i32,
+            width: position.width.floor() as i32,
+            height: position.height.floor() as i32,
+        });
+        preview.popover.popup();
+    });
+}
+
+pub fn hide() {
+    PREVIEW.with(|preview| {
+        if let Some(preview) = preview.borrow().as_ref() {
+            preview.popover.popdown();
+        }
+    });
+}
@@ -0,0 +1,440 @@
+/* Copyright (C) 2022 Purism SPC
+ * SPDX-License-Identifier: GPL-3.0+
+ */
+
+/*! Recording and replaying input sessions.
+ *
+ * Recording is enabled by setting the `SQUEEKBOARD_RECORD` environment variable
+ * to the path of a file.
+ * Every line of the file is a JSON-encoded `Entry`.
+ *
+ * Nothing is recorded while a privacy-sensitive field is in use:
+ * neither the touches, nor what got submitted.
+ * Instead, a single `Entry::Redacted` marks the gap.
+ * State events are recorded always, but they never carry any text.
+ *
+ * Replaying feeds the touches through a fresh `Layout` and `Submission`
+ * with in-memory backends,
+ * and compares what gets submitted with what was recorded.
+ */
+
+use serde::{ Deserialize, Serialize };
+use std::cell::Cell;
+use std::env;
+use std::fmt;
+use std::fs::{ File, OpenOptions };
+use std::io;
+use std::io::{ BufRead, BufReader, Write };
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::{ Arc, Mutex };
+
+use crate::data::loading;
+use crate::imservice::{ ContentHint, ContentPurpose };
+use crate::layout;
+use crate::layout::ArrangementKind;
+use crate::logging;
+use crate::submission::{ memory, Submission, Timestamp };
+
+// Traits
+use crate::logging::Warn;
+use std::convert::TryFrom;
+
+
+/// Something sent out of the keyboard
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Submitted {
+    /// Committed through the input method
+    Text(String),
+    /// A key event on the virtual keyboard
+    Key {
+        /// Index of the keymap within the layout
+        keymap: usize,
+        code: u32,
+        pressed: bool,
+    },
+    /// Modifier bits on the virtual keyboard
+    Modifiers(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    /// A new layout got loaded
+    Layout {
+        name: String,
+        /// `ArrangementKind` value
+        kind: u32,
+        /// `ContentPurpose` value
+        purpose: u32,
+        overlay: Option<String>,
+    },
+    /// A text field got focused or unfocused
+    InputMethod {
+        active: bool,
+        /// `ContentPurpose` value
+        purpose: u32,
+        /// `ContentHint` bits
+        hint: u32,
+    },
+    /// The view got changed other than by pressing buttons
+    View { name: String },
+    /// Touch down, in layout coordinates
+    Press { x: f64, y: f64, time: u32 },
+    /// Touch point moved, in layout coordinates
+    Drag { x: f64, y: f64, time: u32 },
+    /// Touch up
+    Release { time: u32 },
+    /// Event sent to the state manager, for information only
+    State { event: String },
+    Submitted { action: Submitted },
+    /// Entries were left out because a privacy-sensitive field was in use
+    Redacted,
+}
+
+enum Sink {
+    File(File),
+    Memory(Vec<Entry>),
+}
+
+struct Inner {
+    sink: Sink,
+    /// The last entry was Redacted, so there's no need for another one
+    redacting: bool,
+}
+
+/// Writes entries to a file or to memory.
+/// Clones write to the same place.
+#[derive(Clone)]
+pub struct Recorder(Arc<Mutex<Inner>>);
+
+impl Recorder {
+    fn new(sink: Sink) -> Self {
+        Recorder(Arc::new(Mutex::new(Inner { sink, redacting: false })))
+    }
+
+    pub fn to_file(path: &Path) -> io::Result<Self> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(|file| Self::new(Sink::File(file)))
+    }
+
+    pub fn in_memory() -> Self {
+        Self::new(Sink::Memory(Vec::new()))
+    }
+
+    /// Opens the file from `SQUEEKBOARD_RECORD`, if requested
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os("SQUEEKBOARD_RECORD")?;
+        match Self::to_file(Path::new(&path)) {
+            Ok(recorder) => {
+                log_print!(
+                    logging::Level::Info,
+                    "Recording input to {:?}", path,
+                );
+                Some(recorder)
+            },
+            Err(e) => {
+                log_print!(
+                    logging::Level::Warning,
+                    "Can't record input to {:?}: {}", path, e,
+                );
+                None
+            },
+        }
+    }
+
+    pub fn record(&self, entry: Entry) {
+        self.record_unless_private(entry, false)
+    }
+
+    /// When `private` is set, the entry gets replaced with `Entry::Redacted`.
+    pub fn record_unless_private(&self, entry: Entry, private: bool) {
+        let mut inner = match self.0.lock() {
+            Ok(inner) => inner,
+            Err(e) => {
+                log_print!(
+                    logging::Level::Bug,
+                    "Recorder poisoned: {}", e,
+                );
+                return;
+            },
+        };
+        let entry = match (private, inner.redacting) {
+            (true, true) => return,
+            (true, false) => Entry::Redacted,
+            (false, _) => entry,
+        };
+        inner.redacting = private;
+        match &mut inner.sink {
+            Sink::Memory(entries) => entries.push(entry),
+            Sink::File(file) => {
+                let result = serde_json::to_string(&entry)
+                    .map_err(io::Error::from)
+                    .and_then(|line| writeln!(file, "{}", line));
+                if let Err(e) = result {
+                    log_print!(
+                        logging::Level::Warning,
+                        "Can't write recording: {}", e,
+                    );
+                }
+            },
+        }
+    }
+
+    /// Returns the entries recorded in memory.
+    /// Recordings to files are not read back.
+    pub fn get_entries(&self) -> Vec<Entry> {
+        match self.0.lock() {
+            Ok(inner) => match &inner.sink {
+                Sink::Memory(entries) => entries.clone(),
+                Sink::File(_) => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// The recorder for things not tied to a single Submission,
+/// like state events.
+static GLOBAL: Mutex<Option<Recorder>> = Mutex::new(None);
+
+pub fn set_global(recorder: Option<Recorder>) {
+    match GLOBAL.lock() {
+        Ok(mut global) => *global = recorder,
+        Err(_) => log_print!(
+            logging::Level::Bug,
+            "Global recorder poisoned",
+        ),
+    }
+}
+
+/// Calls `f` only if recording is on,
+/// so that entries don't get built needlessly.
+pub fn with_global<F: FnOnce(&Recorder)>(f: F) {
+    // The lock is not held while recording,
+    // in case recording needs the global recorder again.
+    let recorder = GLOBAL.lock().ok()
+        .and_then(|global| global.clone());
+    if let Some(recorder) = recorder {
+        f(&recorder)
+    }
+}
+
+fn arrangement_from_u32(kind: u32) -> Option<ArrangementKind> {
+    match kind {
+        0 => Some(ArrangementKind::Base),
+        1 => Some(ArrangementKind::Wide),
+        2 => Some(ArrangementKind::Split),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// Touches came before any layout got loaded
+    NoLayout,
+    BadLayout(Entry),
+    Io(io::Error),
+    Parse { line: usize, error: serde_json::Error },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::NoLayout => write!(f, "Touch recorded before any layout"),
+            ReplayError::BadLayout(entry) => write!(f, "Unusable layout entry: {:?}", entry),
+            ReplayError::Io(e) => write!(f, "Can't read recording: {}", e),
+            ReplayError::Parse { line, error }
+                => write!(f, "Bad entry on line {}: {}", line, error),
+        }
+    }
+}
+
+/// What got submitted when replaying
+#[derive(Debug)]
+pub struct Report {
+    pub expected: Vec<Submitted>,
+    pub actual: Vec<Submitted>,
+    /// How many gaps caused by privacy-sensitive fields were skipped
+    pub redacted: usize,
+}
+
+impl Report {
+    pub fn matches(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.redacted > 0 {
+            writeln!(f, "Skipped {} redacted gaps", self.redacted)?;
+        }
+        match self.matches() {
+            true => write!(f, "Replay matches: {} actions", self.actual.len()),
+            false => {
+                writeln!(f, "Replay differs.")?;
+                writeln!(f, "Recorded: {:?}", self.expected)?;
+                write!(f, "Replayed: {:?}", self.actual)
+            },
+        }
+    }
+}
+
+pub fn replay(entries: &[Entry]) -> Result<Report, ReplayError> {
+    let recorder = Recorder::in_memory();
+    let log = memory::new_log();
+    let focus: memory::Focus = Rc::new(Cell::new(None));
+    let mut submission = Submission::new(
+        Box::new(memory::VirtualKeyboard::new(log.clone())),
+        Some(Box::new(memory::InputMethod::new(log, focus.clone()))),
+        Some(recorder.clone()),
+    );
+    let mut current: Option<layout::Layout> = None;
+    let mut expected = Vec::new();
+    let mut redacted = 0;
+
+    for entry in entries {
+        match entry {
+            Entry::Layout { name, kind, purpose, overlay } => {
+                let (kind, purpose) = match (
+                    arrangement_from_u32(*kind),
+                    ContentPurpose::try_from(*purpose),
+                ) {
+                    (Some(kind), Ok(purpose)) => (kind, purpose),
+                    _ => return Err(ReplayError::BadLayout(entry.clone())),
+                };
+                let new_layout = loading::load_layout(name, kind, purpose, overlay);
+                submission.use_layout(&new_layout.shape, Timestamp(0));
+                current = Some(new_layout);
+            },
+            Entry::InputMethod { active, purpose, hint } => {
+                focus.set(match active {
+                    true => Some(memory::TextField {
+                        purpose: ContentPurpose::try_from(*purpose)
+                            .unwrap_or(ContentPurpose::Normal),
+                        hint: ContentHint::from_bits_truncate(*hint),
+                    }),
+                    false => None,
+                });
+            },
+            Entry::View { name } => {
+                if let Some(layout) = current.as_mut() {
+                    layout::procedures::set_view(layout, name.clone())
+                        .or_print(
+                            logging::Problem::Warning,
+                            "Can't replay view change",
+                        );
+                }
+            },
+            Entry::Press { x, y, time } => {
+                let layout = current.as_mut().ok_or(ReplayError::NoLayout)?;
+                layout::procedures::press(
+                    layout,
+                    &mut submission,
+                    layout::c::Point { x: *x, y: *y },
+                    Timestamp(*time),
+                );
+            },
+            Entry::Drag { x, y, time } => {
+                let layout = current.as_mut().ok_or(ReplayError::NoLayout)?;
+                layout::procedures::drag(
+                    layout,
+                    &mut submission,
+                    None,
+                    None,
+                    layout::c::Point { x: *x, y: *y },
+                    Timestamp(*time),
+                );
+            },
+            Entry::Release { time } => {
+                if let Some(layout) = current.as_mut() {
+                    layout::procedures::release(
+                        layout,
+                        &mut submission,
+                        None,
+                        None,
+                        Timestamp(*time),
+                    );
+                }
+            },
+            Entry::Submitted { action } => expected.push(action.clone()),
+            // What happened in between is unknown,
+            // so the replay may diverge after this.
+            Entry::Redacted => redacted += 1,
+            Entry::State { .. } => {},
+        }
+    }
+
+    let actual = recorder.get_entries().into_iter()
+        .filter_map(|entry| match entry {
+            Entry::Submitted { action } => Some(action),
+            _ => None,
+        })
+        .collect();
+    Ok(Report {
+        expected,
+        actual,
+        redacted,
+    })
+}
+
+pub fn read_file(path: &Path) -> Result<Vec<Entry>, ReplayError> {
+    let file = File::open(path).map_err(ReplayError::Io)?;
+    BufReader::new(file).lines()
+        .enumerate()
+        .filter(|(_, line)| match line {
+            Ok(line) => !line.trim().is_empty(),
+            Err(_) => true,
+        })
+        .map(|(index, line)| {
+            let line = line.map_err(ReplayError::Io)?;
+            serde_json::from_str(&line)
+                .map_err(|error| ReplayError::Parse { line: index + 1, error })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entry_roundtrip() {
+        let entries = vec![
+            Entry::Press { x: 1.5, y: 2.0, time: 10 },
+            Entry::Submitted {
+                action: Submitted::Key { keymap: 0, code: 38, pressed: true },
+            },
+            Entry::Redacted,
+        ];
+        for entry in entries {
+            let line = serde_json::to_string(&entry).unwrap();
+            assert_eq!(serde_json::from_str::<Entry>(&line).unwrap(), entry);
+        }
+    }
+
+    #[test]
+    fn private_entries_redacted() {
+        let recorder = Recorder::in_memory();
+        recorder.record(Entry::Release { time: 0 });
+        recorder.record_unless_private(Entry::Release { time: 1 }, true);
+        recorder.record_unless_private(
+            Entry::Submitted { action: Submitted::Text("secret".into()) },
+            true,
+        );
+        recorder.record(Entry::Release { time: 2 });
+        assert_eq!(
+            recorder.get_entries(),
+            vec![
+                Entry::Release { time: 0 },
+                Entry::Redacted,
+                Entry::Release { time: 2 },
+            ],
+        );
+    }
+}
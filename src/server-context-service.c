@@ -46,7 +46,7 @@ server_context_service_set_property (GObject      *object,
 
     switch (prop_id) {
     case PROP_ENABLED:
-        squeek_state_send_keyboard_present(self->state_manager, !g_value_get_boolean (value));
+        squeek_state_send_enabled(self->state_manager, g_value_get_boolean (value));
         break;
     default:
         G_OBJECT_WARN_INVALID_PROPERTY_ID (object, prop_id, pspec);
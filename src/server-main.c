@@ -128,6 +128,9 @@ registry_handle_global (void *data,
     } else if (!strcmp(interface, "wl_seat")) {
         wayland->seat = wl_registry_bind(registry, name,
             &wl_seat_interface, 1);
+    } else if (!strcmp(interface, wp_fractional_scale_manager_v1_interface.name)) {
+        wayland->fractional_scale_manager = wl_registry_bind(registry, name,
+            &wp_fractional_scale_manager_v1_interface, 1);
     }
 }
 
@@ -415,7 +418,10 @@ main (int argc, char **argv)
     guint owner_id = 0;
     DBusHandler *service = NULL;
     if (connection) {
-        service = dbus_handler_new(connection, DBUS_SERVICE_PATH, rsobjects.state_manager);
+        service = dbus_handler_new(connection, DBUS_SERVICE_PATH,
+                                   rsobjects.state_manager,
+                                   instance.settings_context,
+                                   rsobjects.submission);
 
         if (service == NULL) {
             g_printerr ("Can't create dbus server\n");
@@ -451,7 +457,7 @@ main (int argc, char **argv)
         rsobjects.state_manager,
         rsobjects.popover);
 
-    register_ui_loop_handler(rsobjects.receiver, &instance.panel_manager, rsobjects.popover, instance.settings_context, instance.dbus_handler);
+    register_ui_loop_handler(rsobjects.receiver, &instance.panel_manager, rsobjects.popover, instance.settings_context, instance.dbus_handler, rsobjects.state_manager);
 
     session_register();
 
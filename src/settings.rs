@@ -0,0 +1,603 @@
+/*! Access to gsettings, both squeekboard's own and system-wide ones.
+ *
+ * Squeekboard's own settings live in the `sm.puri.Squeekboard` schema.
+ * When the schema is not installed, the defaults are used.
+ */
+
+use gio;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::logging;
+
+// Traits
+use gio::prelude::SettingsExt;
+use crate::logging::Warn;
+
+/// Squeekboard's own schema
+const SCHEMA: &str = "sm.puri.Squeekboard";
+
+/// Returns None if the schema is not installed,
+/// instead of crashing like `gio::Settings::new` would.
+pub fn get_settings(schema_name: &str) -> Option<gio::Settings> {
+    let mut error_handler = logging::Print{};
+
+    #[cfg(feature = "glib_v0_14")]
+    let ss = gio::SettingsSchemaSource::default();
+    #[cfg(not(feature = "glib_v0_14"))]
+    let ss = gio::SettingsSchemaSource::get_default();
+
+    ss.or_warn(
+            &mut error_handler,
+            logging::Problem::Surprise,
+            "No gsettings schemas installed.",
+        )
+        .and_then(|sss|
+            sss.lookup(schema_name, true)
+                .or_warn(
+                    &mut error_handler,
+                    logging::Problem::Surprise,
+                    &format!("Gsettings schema {} not installed", schema_name),
+                )
+        )
+        .map(|_sschema| gio::Settings::new(schema_name))
+}
+
+thread_local! {
+    /// gio::Settings keeps itself up to date with changes,
+    /// so one instance is enough.
+    /// It's not thread-safe, so it only lives on the UI thread.
+    static OWN: Option<gio::Settings> = get_settings(SCHEMA);
+    /// System-wide desktop interface preferences
+    static INTERFACE: Option<gio::Settings>
+        = get_settings("org.gnome.desktop.interface");
+    /// System-wide accessibility preferences
+    static A11Y_INTERFACE: Option<gio::Settings>
+        = get_settings("org.gnome.desktop.a11y.interface");
+}
+
+/// Calls `f` with squeekboard's own settings, if they are installed.
+pub fn with_own<T, F: FnOnce(&gio::Settings) -> T>(f: F) -> Option<T> {
+    OWN.with(|settings| settings.as_ref().map(f))
+}
+
+/// Configuration of the swipe gesture hiding the panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HideSwipe {
+    pub enabled: bool,
+    /// Fraction of the layout height the touch point must travel down
+    pub distance: f64,
+}
+
+impl Default for HideSwipe {
+    fn default() -> Self {
+        Self { enabled: true, distance: 0.5 }
+    }
+}
+
+pub fn get_hide_swipe() -> HideSwipe {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let (enabled, distance) = (
+            settings.boolean("hide-swipe"),
+            settings.double("hide-swipe-distance"),
+        );
+        #[cfg(not(feature = "glib_v0_14"))]
+        let (enabled, distance) = (
+            settings.get_boolean("hide-swipe"),
+            settings.get_double("hide-swipe-distance"),
+        );
+        HideSwipe { enabled, distance }
+    }).unwrap_or_default()
+}
+
+/// Whether pressed keys are shown enlarged above the finger
+pub fn get_key_preview() -> bool {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let enabled = settings.boolean("key-preview");
+        #[cfg(not(feature = "glib_v0_14"))]
+        let enabled = settings.get_boolean("key-preview");
+        enabled
+    }).unwrap_or(false)
+}
+
+/// Whether the space bar can move the cursor
+pub fn get_space_trackpad() -> bool {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let enabled = settings.boolean("space-trackpad");
+        #[cfg(not(feature = "glib_v0_14"))]
+        let enabled = settings.get_boolean("space-trackpad");
+        enabled
+    }).unwrap_or(true)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Tests have no schema, so they turn gesture typing on here.
+    pub static GESTURE_TYPING: std::cell::Cell<bool> = std::cell::Cell::new(false);
+}
+
+/// Whether words can be typed by gliding across letters
+pub fn get_gesture_typing() -> bool {
+    #[cfg(test)]
+    {
+        if GESTURE_TYPING.with(|enabled| enabled.get()) {
+            return true;
+        }
+    }
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let enabled = settings.boolean("gesture-typing");
+        #[cfg(not(feature = "glib_v0_14"))]
+        let enabled = settings.get_boolean("gesture-typing");
+        enabled
+    }).unwrap_or(false)
+}
+
+/// A requested panel height
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Height {
+    /// Derived from the pixel density and the screen width
+    #[default]
+    Auto,
+    Millimeter(u32),
+    /// Of the output height
+    Percent(u32),
+}
+
+/// Parses "auto", "40mm", or "35%".
+impl FromStr for Height {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let parse_number = |n: &str| n.trim().parse::<u32>()
+            .map_err(|e| format!("Bad height {}: {}", s, e));
+        if s == "auto" {
+            Ok(Height::Auto)
+        } else if let Some(mm) = s.strip_suffix("mm") {
+            match parse_number(mm)? {
+                0 => Err(format!("Height {} out of range", s)),
+                mm => Ok(Height::Millimeter(mm)),
+            }
+        } else if let Some(percent) = s.strip_suffix('%') {
+            match parse_number(percent)? {
+                p if p > 0 && p <= 100 => Ok(Height::Percent(p)),
+                _ => Err(format!("Height {} out of range", s)),
+            }
+        } else {
+            Err(format!("Bad height {}, expected auto, mm, or %", s))
+        }
+    }
+}
+
+/// The panel height, by screen orientation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PanelHeight {
+    pub portrait: Height,
+    pub landscape: Height,
+}
+
+pub fn get_panel_height() -> PanelHeight {
+    with_own(|settings| {
+        let get = |key: &str| {
+            #[cfg(feature = "glib_v0_14")]
+            let value = settings.string(key);
+            #[cfg(not(feature = "glib_v0_14"))]
+            let value = settings.get_string(key)
+                .map(|s| s.to_string())
+                .unwrap_or_default();
+            value.parse()
+                .or_print(
+                    logging::Problem::Warning,
+                    &format!("Ignoring setting {}", key),
+                )
+                .unwrap_or_default()
+        };
+        PanelHeight {
+            portrait: get("height-portrait"),
+            landscape: get("height-landscape"),
+        }
+    }).unwrap_or_default()
+}
+
+/// Calls `f` whenever one of `keys` in `settings` changes.
+fn connect_keys_in<F: Fn() + 'static>(
+    settings: &gio::Settings,
+    keys: &'static [&'static str],
+    f: F,
+) {
+    let handler = move |_settings: &gio::Settings, key: &str| {
+        if keys.contains(&key) {
+            f()
+        }
+    };
+    #[cfg(feature = "glib_v0_14")]
+    settings.connect_changed(None, handler);
+    #[cfg(not(feature = "glib_v0_14"))]
+    settings.connect_changed(handler);
+}
+
+/// Calls `f` whenever one of `keys` of squeekboard's own settings changes.
+fn connect_keys<F: Fn() + 'static>(keys: &'static [&'static str], f: F) {
+    with_own(|settings| connect_keys_in(settings, keys, f));
+}
+
+/// Calls `f` with the new panel height whenever it changes.
+pub fn connect_panel_height<F: Fn(PanelHeight) + 'static>(f: F) {
+    connect_keys(
+        &["height-portrait", "height-landscape"],
+        move || f(get_panel_height()),
+    );
+}
+
+/// Which edge the compact keyboard sticks to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// Shrinking the layout to be reachable with one thumb
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OneHanded {
+    pub enabled: bool,
+    pub side: Side,
+    /// Fraction of the panel width taken by the layout
+    pub width: f64,
+}
+
+impl Default for OneHanded {
+    fn default() -> Self {
+        Self { enabled: false, side: Side::Right, width: 0.75 }
+    }
+}
+
+pub fn get_one_handed() -> OneHanded {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let (enabled, side, width) = (
+            settings.boolean("one-handed"),
+            settings.string("one-handed-side").to_string(),
+            settings.double("one-handed-width"),
+        );
+        #[cfg(not(feature = "glib_v0_14"))]
+        let (enabled, side, width) = (
+            settings.get_boolean("one-handed"),
+            settings.get_string("one-handed-side")
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+            settings.get_double("one-handed-width"),
+        );
+        OneHanded {
+            enabled,
+            side: match side.as_str() {
+                "left" => Side::Left,
+                _ => Side::Right,
+            },
+            width,
+        }
+    }).unwrap_or_default()
+}
+
+/// Stores the enabled state and the side, so that they survive restarts.
+pub fn set_one_handed(config: OneHanded) {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        {
+            settings.set_boolean("one-handed", config.enabled)
+                .or_print(logging::Problem::Warning, "Can't store one-handed mode");
+            settings.set_string("one-handed-side", config.side.as_str())
+                .or_print(logging::Problem::Warning, "Can't store one-handed side");
+        }
+        #[cfg(not(feature = "glib_v0_14"))]
+        {
+            if !settings.set_boolean("one-handed", config.enabled) {
+                log_print!(logging::Level::Warning, "Can't store one-handed mode");
+            }
+            if !settings.set_string("one-handed-side", config.side.as_str()) {
+                log_print!(logging::Level::Warning, "Can't store one-handed side");
+            }
+        }
+    });
+}
+
+/// Panel floating above applications instead of docked to the bottom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Floating {
+    pub enabled: bool,
+    /// Distance from the left edge of the output, in scaled pixels
+    pub x: i32,
+    /// Distance from the bottom edge of the output, in scaled pixels
+    pub y: i32,
+    /// Fraction of the output width
+    pub width: f64,
+}
+
+impl Default for Floating {
+    fn default() -> Self {
+        Self { enabled: false, x: 0, y: 0, width: 0.6 }
+    }
+}
+
+pub fn get_floating() -> Floating {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let (enabled, x, y, width) = (
+            settings.boolean("floating"),
+            settings.int("floating-x"),
+            settings.int("floating-y"),
+            settings.double("floating-width"),
+        );
+        #[cfg(not(feature = "glib_v0_14"))]
+        let (enabled, x, y, width) = (
+            settings.get_boolean("floating"),
+            settings.get_int("floating-x"),
+            settings.get_int("floating-y"),
+            settings.get_double("floating-width"),
+        );
+        Floating { enabled, x, y, width }
+    }).unwrap_or_default()
+}
+
+/// Remembers where the floating panel was moved.
+/// Both coordinates are applied at once,
+/// so that listeners never see a half-updated position.
+pub fn set_floating_position(x: i32, y: i32) {
+    with_own(|settings| {
+        settings.delay();
+        #[cfg(feature = "glib_v0_14")]
+        let stored = settings.set_int("floating-x", x)
+            .and_then(|()| settings.set_int("floating-y", y))
+            .is_ok();
+        #[cfg(not(feature = "glib_v0_14"))]
+        let stored = settings.set_int("floating-x", x)
+            && settings.set_int("floating-y", y);
+        if stored {
+            settings.apply();
+        } else {
+            settings.revert();
+            log_print!(logging::Level::Warning, "Can't store panel position");
+        }
+    });
+}
+
+/// Calls `f` with the new floating configuration whenever it changes.
+pub fn connect_floating<F: Fn(Floating) + 'static>(f: F) {
+    connect_keys(
+        &["floating", "floating-x", "floating-y", "floating-width"],
+        move || f(get_floating()),
+    );
+}
+
+/// Which output the panel appears on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OutputPolicy {
+    /// Whichever comes first
+    #[default]
+    Any,
+    /// Only built-in screens, which are the ones likely to have touch
+    Touch,
+    /// The output of the focused text input, as reported over D-Bus.
+    /// Falls back to Touch.
+    Focus,
+    /// The output with this connector name, like "DSI-1".
+    /// Falls back to Any.
+    Name(String),
+}
+
+/// Older versions only had an environment variable.
+/// It still applies when the setting is at the default.
+const PREFERRED_OUTPUT_VAR: &str = "SQUEEKBOARD_PREFERRED_OUTPUT";
+
+pub fn get_output_policy() -> OutputPolicy {
+    let from_env = || std::env::var(PREFERRED_OUTPUT_VAR).ok()
+        .map(OutputPolicy::Name)
+        .unwrap_or_default();
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let (policy, name) = (
+            settings.string("output-policy").to_string(),
+            settings.string("output-name").to_string(),
+        );
+        #[cfg(not(feature = "glib_v0_14"))]
+        let (policy, name) = (
+            settings.get_string("output-policy")
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+            settings.get_string("output-name")
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+        );
+        match policy.as_str() {
+            "touch" => OutputPolicy::Touch,
+            "focus" => OutputPolicy::Focus,
+            "name" => OutputPolicy::Name(name),
+            _ => from_env(),
+        }
+    }).unwrap_or_else(from_env)
+}
+
+/// Calls `f` with the new output policy whenever it changes.
+pub fn connect_output_policy<F: Fn(OutputPolicy) + 'static>(f: F) {
+    connect_keys(
+        &["output-policy", "output-name"],
+        move || f(get_output_policy()),
+    );
+}
+
+/// How long the panel takes to slide in or out.
+/// Zero when the user asked for reduced motion.
+pub fn get_slide_duration() -> Duration {
+    let animations_enabled = INTERFACE.with(|settings| {
+        settings.as_ref().map(|settings| {
+            #[cfg(feature = "glib_v0_14")]
+            let enabled = settings.boolean("enable-animations");
+            #[cfg(not(feature = "glib_v0_14"))]
+            let enabled = settings.get_boolean("enable-animations");
+            enabled
+        })
+    }).unwrap_or(true);
+    let duration = with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let ms = settings.uint("slide-duration");
+        #[cfg(not(feature = "glib_v0_14"))]
+        let ms = settings.get_uint("slide-duration");
+        Duration::from_millis(ms as u64)
+    }).unwrap_or(Duration::from_millis(150));
+    match animations_enabled {
+        true => duration,
+        false => Duration::from_millis(0),
+    }
+}
+
+/// Calls `f` with the new slide duration whenever it changes.
+pub fn connect_slide_duration<F: Fn(Duration) + 'static>(f: F) {
+    let f = std::rc::Rc::new(f);
+    {
+        let f = f.clone();
+        connect_keys(&["slide-duration"], move || f(get_slide_duration()));
+    }
+    INTERFACE.with(|settings| {
+        if let Some(settings) = settings {
+            connect_keys_in(
+                settings,
+                &["enable-animations"],
+                move || f(get_slide_duration()),
+            );
+        }
+    });
+}
+
+/// What happens to the panel while a physical keyboard is attached
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyboardPolicy {
+    Hide,
+    /// Show as if there was no physical keyboard.
+    /// The default, because laptops and convertibles
+    /// have a keyboard attached all the time.
+    #[default]
+    Show,
+    /// Show only for digits, numbers, phone numbers and PINs
+    Numeric,
+}
+
+pub fn get_keyboard_policy() -> KeyboardPolicy {
+    with_own(|settings| {
+        #[cfg(feature = "glib_v0_14")]
+        let policy = settings.string("physical-keyboard-policy").to_string();
+        #[cfg(not(feature = "glib_v0_14"))]
+        let policy = settings.get_string("physical-keyboard-policy")
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        match policy.as_str() {
+            "hide" => KeyboardPolicy::Hide,
+            "numeric" => KeyboardPolicy::Numeric,
+            _ => KeyboardPolicy::Show,
+        }
+    }).unwrap_or_default()
+}
+
+/// Calls `f` with the new keyboard policy whenever it changes.
+pub fn connect_keyboard_policy<F: Fn(KeyboardPolicy) + 'static>(f: F) {
+    connect_keys(
+        &["physical-keyboard-policy"],
+        move || f(get_keyboard_policy()),
+    );
+}
+
+/// Readability preferences of the desktop
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Accessibility {
+    pub high_contrast: bool,
+    /// Multiplier for font sizes
+    pub text_scale: f64,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self { high_contrast: false, text_scale: 1.0 }
+    }
+}
+
+pub fn get_accessibility() -> Accessibility {
+    let high_contrast = A11Y_INTERFACE.with(|settings| {
+        settings.as_ref().map(|settings| {
+            #[cfg(feature = "glib_v0_14")]
+            let enabled = settings.boolean("high-contrast");
+            #[cfg(not(feature = "glib_v0_14"))]
+            let enabled = settings.get_boolean("high-contrast");
+            enabled
+        })
+    });
+    let text_scale = INTERFACE.with(|settings| {
+        settings.as_ref().map(|settings| {
+            #[cfg(feature = "glib_v0_14")]
+            let scale = settings.double("text-scaling-factor");
+            #[cfg(not(feature = "glib_v0_14"))]
+            let scale = settings.get_double("text-scaling-factor");
+            scale
+        })
+    });
+    let default = Accessibility::default();
+    Accessibility {
+        high_contrast: high_contrast.unwrap_or(default.high_contrast),
+        text_scale: text_scale.unwrap_or(default.text_scale),
+    }
+}
+
+/// Calls `f` with the new accessibility preferences whenever they change.
+pub fn connect_accessibility<F: Fn(Accessibility) + 'static>(f: F) {
+    let f = std::rc::Rc::new(f);
+    A11Y_INTERFACE.with(|settings| {
+        if let Some(settings) = settings {
+            let f = f.clone();
+            connect_keys_in(
+                settings,
+                &["high-contrast"],
+                move || f(get_accessibility()),
+            );
+        }
+    });
+    INTERFACE.with(|settings| {
+        if let Some(settings) = settings {
+            connect_keys_in(
+                settings,
+                &["text-scaling-factor"],
+                move || f(get_accessibility()),
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_height() {
+        assert_eq!("auto".parse(), Ok(Height::Auto));
+        assert_eq!(" 40mm".parse(), Ok(Height::Millimeter(40)));
+        assert_eq!("35%".parse(), Ok(Height::Percent(35)));
+        assert!("150%".parse::<Height>().is_err());
+        assert!("0%".parse::<Height>().is_err());
+        assert!("0mm".parse::<Height>().is_err());
+        assert!("40".parse::<Height>().is_err());
+    }
+}
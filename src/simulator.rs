@@ -0,0 +1,317 @@
+/* Copyright (C) 2022 Purism SPC
+ * SPDX-License-Identifier: GPL-3.0+
+ */
+
+/*! Running layouts without a display, for testing them.
+ *
+ * Taps go through the same code as touches on the screen,
+ * only the text input and the virtual keyboard are kept in memory.
+ * Key events are turned back into keysym names using the layout's keymaps.
+ */
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::loading;
+use crate::imservice::{ ContentHint, ContentPurpose };
+use crate::keyboard::Modifiers;
+use crate::layout;
+use crate::layout::{ ArrangementKind, LatchedState, Layout };
+use crate::submission::{ memory, Submission, Timestamp };
+use crate::submission::memory::Event;
+use std::cell::Cell;
+use std::rc::Rc;
+use xkbcommon::xkb;
+
+
+/// Time between the press and the release of a tap.
+/// Well below a long press.
+const TAP_MS: u32 = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tap {
+    /// Coordinates in the layout
+    At { x: f64, y: f64 },
+    /// Name of a button in the current view, as in the layout file
+    Button(String),
+}
+
+/// Parses "@x,y" as coordinates, anything else as a button name.
+impl FromStr for Tap {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.strip_prefix('@') {
+            Some(coords) => {
+                let mut parts = coords.splitn(2, ',')
+                    .map(|part| part.trim().parse::<f64>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(x)), Some(Ok(y))) => Ok(Tap::At { x, y }),
+                    _ => Err(Error::BadCoordinates(s.into())),
+                }
+            },
+            None => Ok(Tap::Button(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for Tap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tap::At { x, y } => write!(f, "@{},{}", x, y),
+            Tap::Button(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    NoSuchButton { name: String, view: String },
+    BadCoordinates(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoSuchButton { name, view }
+                => write!(f, "No button {} in view {}", name, view),
+            Error::BadCoordinates(s)
+                => write!(f, "Expected coordinates like @10.5,20, got {}", s),
+        }
+    }
+}
+
+/// The outcome of a single tap
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// Committed text, and names of keys in angle brackets
+    pub output: String,
+    /// The view after the tap
+    pub view: String,
+    /// The view to return to when the latch pops, if latched
+    pub latched_from: Option<String>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "output: {:?}, view: {}", self.output, self.view)?;
+        if let Some(view) = &self.latched_from {
+            write!(f, ", latched from: {}", view)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Simulator {
+    layout: Layout,
+    submission: Submission,
+    log: memory::Log,
+    /// One per layout keymap, to name the submitted keys
+    keymaps: Vec<Option<xkb::State>>,
+    keymap_idx: usize,
+    modifiers: Modifiers,
+    time: u32,
+    /// Everything output so far
+    output: String,
+}
+
+impl Simulator {
+    /// Loads the layout the same way as squeekboard would,
+    /// including any fallbacks.
+    /// Text gets submitted to a text field with the given purpose.
+    pub fn new(name: &str, wide: bool, purpose: ContentPurpose) -> Self {
+        let kind = match wide {
+            true => ArrangementKind::Wide,
+            false => ArrangementKind::Base,
+        };
+        let layout = loading::load_layout(&name.into(), kind, purpose, &None);
+        let log = memory::new_log();
+        let focus = Rc::new(Cell::new(Some(memory::TextField {
+            purpose,
+            hint: ContentHint::NONE,
+        })));
+        let mut submission = Submission::new(
+            Box::new(memory::VirtualKeyboard::new(log.clone())),
+            Some(Box::new(memory::InputMethod::new(log.clone(), focus))),
+            None,
+        );
+        submission.use_layout(&layout.shape, Timestamp(0));
+        let keymaps = layout.shape.keymaps.iter()
+            .map(|keymap_str| {
+                let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+                xkb::Keymap::new_from_string(
+                    &context,
+                    keymap_str.to_string_lossy().into_owned(),
+                    xkb::KEYMAP_FORMAT_TEXT_V1,
+                    xkb::KEYMAP_COMPILE_NO_FLAGS,
+                ).map(|keymap| xkb::State::new(&keymap))
+            })
+            .collect();
+        log.borrow_mut().clear();
+        Simulator {
+            layout,
+            submission,
+            log,
+            keymaps,
+            keymap_idx: 0,
+            modifiers: Modifiers::empty(),
+            time: 0,
+            output: String::new(),
+        }
+    }
+
+    pub fn get_view(&self) -> &str {
+        &self.layout.state.current_view
+    }
+
+    /// Everything output since the start
+    pub fn get_output(&self) -> &str {
+        &self.output
+    }
+
+    /// Returns the center of the named button in the current view
+    fn find_button(&self, name: &str) -> Result<layout::c::Point, Error> {
+        let (view_offset, view) = self.layout.get_current_view_position();
+        view.get_rows().iter()
+            .flat_map(|(row_offset, row)| {
+                row.get_buttons().iter()
+                    .map(move |(x_offset, button)| (row_offset, x_offset, button))
+            })
+            .find(|(_, _, button)| button.name.to_str() == Ok(name))
+            .map(|(row_offset, x_offset, button)| layout::c::Point {
+                x: view_offset.x + row_offset.x + x_offset + button.size.width / 2.0,
+                y: view_offset.y + row_offset.y + button.size.height / 2.0,
+            })
+            .ok_or_else(|| Error::NoSuchButton {
+                name: name.into(),
+                view: self.get_view().into(),
+            })
+    }
+
+    fn describe_key(&self, code: u32) -> String {
+        let name = self.keymaps.get(self.keymap_idx)
+            .and_then(|state| state.as_ref())
+            .map(|state| xkb::keysym_get_name(state.key_get_one_sym(code)))
+            .unwrap_or_else(|| format!("keycode {}", code));
+        let modifiers = [
+            (Modifiers::CONTROL, "Control+"),
+            (Modifiers::MOD1, "Alt+"),
+            (Modifiers::MOD4, "Super+"),
+        ].iter()
+            .filter(|(modifier, _)| self.modifiers.contains(*modifier))
+            .map(|(_, name)| *name)
+            .collect::<String>();
+        format!("<{}{}>", modifiers, name)
+    }
+
+    pub fn tap(&mut self, tap: &Tap) -> Result<Step, Error> {
+        let point = match tap {
+            Tap::At { x, y } => layout::c::Point { x: *x, y: *y },
+            Tap::Button(name) => self.find_button(name)?,
+        };
+
+        layout::procedures::press(
+            &mut self.layout,
+            &mut self.submission,
+            point,
+            Timestamp(self.time),
+        );
+        self.time += TAP_MS;
+        layout::procedures::release(
+            &mut self.layout,
+            &mut self.submission,
+            None,
+            None,
+            Timestamp(self.time),
+        );
+        self.time += TAP_MS;
+
+        let events: Vec<Event> = self.log.borrow_mut().drain(..).collect();
+        let mut output = String::new();
+        for event in events {
+            match event {
+                Event::CommitString(text) => output.push_str(&text),
+                Event::Key { code, pressed: true }
+                    => output.push_str(&self.describe_key(code)),
+                Event::Keymap(idx) => self.keymap_idx = idx,
+                Event::Modifiers(modifiers) => self.modifiers = modifiers,
+                _ => {},
+            }
+        }
+        self.output.push_str(&output);
+
+        Ok(Step {
+            output,
+            view: self.get_view().into(),
+            latched_from: match self.layout.get_view_latched() {
+                LatchedState::FromView(view) => Some(view.clone()),
+                LatchedState::Not => None,
+            },
+        })
+    }
+
+    /// Stops at the first tap that fails
+    pub fn run(&mut self, taps: &[Tap]) -> Result<Vec<Step>, Error> {
+        taps.iter().map(|tap| self.tap(tap)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn taps(names: &[&str]) -> Vec<Tap> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_taps() {
+        assert_eq!("@1.5, 2".parse(), Ok(Tap::At { x: 1.5, y: 2.0 }));
+        assert_eq!("a".parse(), Ok(Tap::Button("a".into())));
+        assert!("@1.5".parse::<Tap>().is_err());
+    }
+
+    #[test]
+    fn shift_latches_once() {
+        let mut sim = Simulator::new("us", false, ContentPurpose::Normal);
+        let steps = sim.run(&taps(&["Shift_L", "A", "b"])).unwrap();
+        assert_eq!(steps[0].view, "upper");
+        assert_eq!(steps[0].latched_from, Some("base".into()));
+        assert_eq!(steps[1].output, "A");
+        assert_eq!(steps[1].view, "base");
+        assert_eq!(steps[1].latched_from, None);
+        assert_eq!(sim.get_output(), "Ab");
+    }
+
+    #[test]
+    fn erase_is_key() {
+        let mut sim = Simulator::new("us", false, ContentPurpose::Normal);
+        let steps = sim.run(&taps(&["BackSpace"])).unwrap();
+        assert_eq!(steps[0].output, "<BackSpace>");
+    }
+
+    #[test]
+    fn tap_by_position() {
+        let mut sim = Simulator::new("us", false, ContentPurpose::Normal);
+        let (offset, view) = sim.layout.get_current_view_position();
+        let (row_offset, row) = &view.get_rows()[0];
+        let (x_offset, button) = &row.get_buttons()[0];
+        let tap = Tap::At {
+            x: offset.x + row_offset.x + x_offset + 1.0,
+            y: offset.y + row_offset.y + 1.0,
+        };
+        let name = button.name.to_str().unwrap().to_owned();
+        assert_eq!(sim.tap(&tap).unwrap().output, name);
+    }
+
+    #[test]
+    fn missing_button() {
+        let mut sim = Simulator::new("us", false, ContentPurpose::Normal);
+        assert_eq!(
+            sim.tap(&Tap::Button("nonexistent".into())),
+            Err(Error::NoSuchButton {
+                name: "nonexistent".into(),
+                view: "base".into(),
+            }),
+        );
+    }
+}
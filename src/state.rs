@@ -11,6 +11,7 @@ use crate::event_loop;
 use crate::event_loop::ActorState;
 use crate::imservice::{ ContentHint, ContentPurpose };
 use crate::layout::ArrangementKind;
+use crate::logging;
 use crate::main;
 use crate::main::Commands;
 use crate::outputs;
@@ -18,11 +19,14 @@ use crate::outputs::{Millimeter, OutputId, OutputState};
 use crate::panel;
 use crate::panel::PixelSize;
 use crate::popover;
+use crate::settings;
 use crate::util::Rational;
 use std::cmp;
 use std::collections::HashMap;
-use std::env;
-use std::time::Instant;
+use std::time::{ Duration, Instant };
+
+// Traits
+use crate::logging::Warn;
 
 
 #[derive(Clone, Copy, Debug)]
@@ -73,10 +77,36 @@ pub enum Event {
     InputMethod(InputMethod),
     Visibility(visibility::Event),
     PhysicalKeyboard(Presence),
+    /// The user turned the on-screen keyboard on or off in accessibility settings
+    ScreenKeyboardEnabled(bool),
+    KeyboardPolicy(settings::KeyboardPolicy),
     Output(outputs::Event),
     LayoutChoice(LayoutChoice),
     OverlayChanged(popover::LayoutId),
+    /// A view of the current layout got requested from outside
+    ViewChoice(String),
+    /// The requested view with this serial got shown
+    ViewApplied(u32),
     Debug(debug::Event),
+    /// The user changed the preferred panel height
+    PanelHeight(settings::PanelHeight),
+    /// The user changed the floating mode or moved the panel
+    Floating(settings::Floating),
+    /// The compositor decided the size of the docked panel
+    PanelConfigured {
+        output: OutputId,
+        size: panel::Size,
+    },
+    /// The compositor prefers a fractional scale for the panel
+    PanelScale {
+        output: OutputId,
+        scale: panel::Scale,
+    },
+    OutputPolicy(settings::OutputPolicy),
+    /// Zero disables the animation
+    SlideDuration(Duration),
+    /// Name of the output with the focused text input, if known
+    FocusedOutput(Option<String>),
     /// Event triggered because a moment in time passed.
     /// Use to animate state transitions.
     /// The value is the ideal arrival time.
@@ -133,6 +163,7 @@ pub mod visibility {
 pub struct Outcome {
     pub panel: animation::Outcome,
     pub im: InputMethod,
+    pub view: Option<ViewChoice>,
 }
 
 impl event_loop::Outcome for Outcome {
@@ -144,9 +175,26 @@ impl event_loop::Outcome for Outcome {
     /// of checking if the commands end up being no-ops.
     fn get_commands_to_reach(&self, new_state: &Self) -> Commands {
 // FIXME: handle switching outputs
+        let panel_offset = match new_state.panel {
+            animation::Outcome::Visible{offset, ..} => Some(offset),
+            animation::Outcome::Hidden => None,
+        };
         let (dbus_visible_set, panel_visibility) = match new_state.panel {
-            animation::Outcome::Visible{output, height, ..}
-                => (Some(true), Some(panel::Command::Show{output, height})),
+            animation::Outcome::Visible{
+                output,
+                height,
+                placement: animation::Placement::Docked,
+                ..
+            } => (Some(true), Some(panel::Command::Show{output, height})),
+            animation::Outcome::Visible{
+                output,
+                height,
+                placement: animation::Placement::Floating{width, position},
+                ..
+            } => (
+                Some(true),
+                Some(panel::Command::ShowFloating{output, width, height, position}),
+            ),
             animation::Outcome::Hidden => (Some(false), Some(panel::Command::Hide)),
         };
 
@@ -173,14 +221,34 @@ impl event_loop::Outcome for Outcome {
             animation::Outcome::Hidden => None,
         };        
 
+        // A new layout starts at the base view,
+        // so a view not confirmed yet gets applied again.
+        let view_selection = match (&new_state.panel, &new_state.view) {
+            (Visible{..}, Some(view))
+                if layout_selection.is_some() || self.view.as_ref() != Some(view)
+            => Some(view.clone()),
+            _ => None,
+        };
+
         Commands {
+            panel_offset,
             panel_visibility,
             dbus_visible_set,
             layout_selection,
+            view_selection,
         }
     }
 }
 
+/// A view requested from outside
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewChoice {
+    pub name: String,
+    /// Tells apart repeated requests,
+    /// so that a late confirmation doesn't drop a newer one.
+    pub serial: u32,
+}
+
 /// The actual logic of the program.
 /// At this moment, limited to calculating visibility and IM hints.
 ///
@@ -197,13 +265,15 @@ pub struct Application {
     pub im: InputMethod,
     pub visibility_override: visibility::State,
     pub physical_keyboard: Presence,
+    pub keyboard_policy: settings::KeyboardPolicy,
+    pub screen_keyboard_enabled: bool,
     pub debug_mode_enabled: bool,
+    pub output_policy: settings::OutputPolicy,
+    pub focused_output_name: Option<String>,
     /// The output on which the panel should appear.
     /// This is stored as part of the state
-    /// because it's not clear how to derive the output from the rest of the state.
-    /// It should probably follow the focused input,
-    /// but not sure about being allowed on non-touch displays.
-    pub preferred_output_name: Option<String>,
+    /// because the policy prefers to stay on the current output
+    /// as long as it qualifies.
     pub preferred_output: Option<OutputId>,
     pub outputs: HashMap<OutputId, OutputState>,
     /// We presume that the system always has some preference,
@@ -213,8 +283,30 @@ pub struct Application {
     pub layout_choice: LayoutChoice,
     /// Manual override of the system layout
     pub overlay_layout: Option<popover::LayoutId>,
+    /// Kept until the view gets shown,
+    /// after that the user is free to switch views.
+    pub view_choice: Option<ViewChoice>,
+    /// Counts view requests
+    pub view_serial: u32,
+    pub panel_height: settings::PanelHeight,
+    pub floating: settings::Floating,
+    /// The size last given to the docked panel by the compositor.
+    /// It's only valid until the output changes.
+    pub panel_allocation: Option<(OutputId, panel::Size)>,
+    /// Only known if the compositor supports fractional scaling.
+    pub panel_scale: Option<(OutputId, panel::Scale)>,
+    pub slide_duration: Duration,
+    /// When the panel started sliding in or out
+    pub panel_transition: animation::Transition,
 }
 
+/// Outputs at least as wide in landscape get the split arrangement.
+/// Around the width of a 10" tablet.
+const SPLIT_MIN_WIDTH: Millimeter = Millimeter(200);
+/// Wider outputs are monitors rather than something held in hands.
+/// Around the width of a 15" convertible.
+const SPLIT_MAX_WIDTH: Millimeter = Millimeter(350);
+
 impl Application {
     /// A conservative default, ignoring the actual state of things.
     /// It will initially show the keyboard for a blink.
@@ -227,9 +319,11 @@ impl Application {
             im: InputMethod::InactiveSince(now),
             visibility_override: visibility::State::NotForced,
             physical_keyboard: Presence::Missing,
+            keyboard_policy: Default::default(),
+            screen_keyboard_enabled: true,
             debug_mode_enabled: false,
-            preferred_output_name: env::var_os("SQUEEKBOARD_PREFERRED_OUTPUT")
-                .and_then(|v| v.into_string().ok()),
+            output_policy: Default::default(),
+            focused_output_name: None,
             preferred_output: None,
             outputs: Default::default(),
             layout_choice: LayoutChoice {
@@ -237,6 +331,19 @@ impl Application {
                 source: LayoutSource::Xkb,
             },
             overlay_layout: None,
+            view_choice: None,
+            view_serial: 0,
+            panel_height: Default::default(),
+            floating: Default::default(),
+            panel_allocation: None,
+            panel_scale: None,
+            // Until the settings arrive, don't animate.
+            slide_duration: Duration::from_millis(0),
+            // Matches the initial blink.
+            panel_transition: animation::Transition {
+                visible: true,
+                since: now,
+            },
         }
     }
 
@@ -248,14 +355,86 @@ impl Application {
                 event,
             );
         }
-        let state = match event {
-            Event::Debug(dbg) => Self {
-                debug_mode_enabled: match dbg {
-                    debug::Event::Enable => true,
-                    debug::Event::Disable => false,
-                },
+        // Changes caused by time passing only get recorded here,
+        // when they didn't come with a timeout event.
+        let state = self.record_transition(now)
+            .apply_event_unrecorded(event, now)
+            .record_transition(now);
+
+        if state.debug_mode_enabled {
+            println!(
+                "State is now:
+{:#?}
+Outcome:
+{:#?}",
+                state,
+                state.get_outcome(now),
+            );
+        }
+        state
+    }
+
+    /// Animations start when the wanted visibility changes.
+    fn record_transition(self, now: Instant) -> Self {
+        let visible = self.is_wanted_visible(now);
+        match visible == self.panel_transition.visible {
+            true => self,
+            false => Self {
+                panel_transition: animation::Transition { visible, since: now },
                 ..self
             },
+        }
+    }
+
+    fn apply_event_unrecorded(self, event: Event, now: Instant) -> Self {
+        match event {
+            Event::Debug(debug::Event::Enable) => Self {
+                debug_mode_enabled: true,
+                ..self
+            },
+
+            Event::Debug(debug::Event::Disable) => Self {
+                debug_mode_enabled: false,
+                ..self
+            },
+
+            Event::Debug(debug::Event::Dump(reply)) => {
+                reply.send(debug::dump(&self, now))
+                    .or_print(logging::Problem::Warning, "Can't send state dump");
+                self
+            },
+
+            Event::Debug(debug::Event::FakeOutput { name, state }) => {
+                let output = self.outputs.iter()
+                    .find(|(_id, output)| output.name.as_ref() == Some(&name))
+                    .map(|(id, _output)| *id);
+                let change = match (output, state) {
+                    (Some(output), Some(state))
+                        => Some((output, outputs::ChangeType::Altered(state))),
+                    (Some(output), None)
+                        => Some((output, outputs::ChangeType::Removed)),
+                    (None, Some(state)) => {
+                        let output = (1..=u16::MAX)
+                            .map(OutputId::synthetic)
+                            .find(|id| !self.outputs.contains_key(id));
+                        output.map(|output| (output, outputs::ChangeType::Altered(state)))
+                    },
+                    (None, None) => None,
+                };
+                match change {
+                    Some((output, change)) => self.apply_event(
+                        Event::Output(outputs::Event { output, change }),
+                        now,
+                    ),
+                    None => {
+                        log_print!(
+                            logging::Level::Warning,
+                            "No output named {} to fake", name,
+                        );
+                        self
+                    },
+                }
+            },
 
             Event::TimeoutReached(_) => self,
 
@@ -272,46 +451,62 @@ impl Application {
                 ..self
             },
 
+            Event::ScreenKeyboardEnabled(enabled) => Self {
+                screen_keyboard_enabled: enabled,
+                ..self
+            },
+
+            Event::KeyboardPolicy(policy) => Self {
+                keyboard_policy: policy,
+                ..self
+            },
+
             Event::Output(outputs::Event { output, change }) => {
                 let mut app = self;
+                // A rotated or resized output changes the panel size too.
+                if let Some((allocated_output, _)) = app.panel_allocation {
+                    if allocated_output == output {
+                        app.panel_allocation = None;
+                    }
+                }
                 match change {
                     outputs::ChangeType::Altered(state) => {
-                        if app.preferred_output_name.is_some()
-                            && app.preferred_output_name == state.name
-                        {
-                            app.preferred_output = Some(output)
-                        }
-                        app.preferred_output = app.preferred_output.or(Some(output));
                         app.outputs.insert(output, state);
                     },
                     outputs::ChangeType::Removed => {
                         app.outputs.remove(&output);
-                        if app.preferred_output == Some(output) {
-                            app.preferred_output = None;
-                        }
-
-                        if app.preferred_output.is_none() && app.preferred_output_name.is_some() {
-                            app.preferred_output = app
-                                .outputs
-                                .iter()
-                                .filter_map(|(o_id, o_state)| {
-                                    if o_state.name == app.preferred_output_name {
-                                        Some(o_id)
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .next()
-                                .copied()
-                        }
-
-                        if app.preferred_output.is_none() {
-                            // just take whichever comes first.
-                            app.preferred_output = app.outputs.keys().next().map(|output| *output);
+                        if let Some((scaled_output, _)) = app.panel_scale {
+                            if scaled_output == output {
+                                app.panel_scale = None;
+                            }
                         }
                     },
                 };
-                app
+                Self {
+                    preferred_output: app.choose_output(),
+                    ..app
+                }
+            },
+
+            Event::OutputPolicy(output_policy) => {
+                let app = Self { output_policy, ..self };
+                Self {
+                    preferred_output: app.choose_output(),
+                    ..app
+                }
+            },
+
+            Event::SlideDuration(slide_duration) => Self {
+                slide_duration,
+                ..self
+            },
+
+            Event::FocusedOutput(focused_output_name) => {
+                let app = Self { focused_output_name, ..self };
+                Self {
+                    preferred_output: app.choose_output(),
+                    ..app
+                }
             },
 
             Event::InputMethod(new_im)
@@ -357,41 +552,168 @@ impl Application {
             Event::LayoutChoice(layout_choice) => Self {
                 layout_choice,
                 overlay_layout: None,
+                view_choice: None,
                 ..self
             },
             
             Event::OverlayChanged(overlay_layout) => Self {
                 overlay_layout: Some(overlay_layout),
+                view_choice: None,
+                ..self
+            },
+
+            Event::ViewChoice(name) => Self {
+                view_choice: Some(ViewChoice {
+                    name,
+                    serial: self.view_serial,
+                }),
+                view_serial: self.view_serial.wrapping_add(1),
+                ..self
+            },
+
+            Event::ViewApplied(serial) => Self {
+                view_choice: self.view_choice
+                    .filter(|choice| choice.serial != serial),
                 ..self
             },
+
+            Event::PanelHeight(panel_height) => Self {
+                panel_height,
+                ..self
+            },
+
+            Event::Floating(floating) => Self {
+                floating,
+                ..self
+            },
+
+            Event::PanelConfigured { output, size } => Self {
+                panel_allocation: Some((output, size)),
+                ..self
+            },
+
+            Event::PanelScale { output, scale } => Self {
+                panel_scale: Some((output, scale)),
+                ..self
+            },
+        }
+    }
+
+    /// Whether the panel should be shown, disregarding animations
+    fn is_wanted_visible(&self, now: Instant) -> bool {
+        match self.visibility_override {
+            visibility::State::ForcedHidden => false,
+            visibility::State::ForcedVisible => true,
+            visibility::State::NotForced => self.screen_keyboard_enabled
+                && match &self.im {
+                    InputMethod::Active(details)
+                        => self.is_allowed_by_keyboard(Some(details.purpose)),
+                    InputMethod::InactiveSince(since)
+                        => self.is_allowed_by_keyboard(None)
+                            && now < *since + animation::HIDING_TIMEOUT,
+                },
+        }
+    }
+
+    /// Applies the policy for attached physical keyboards
+    fn is_allowed_by_keyboard(&self, purpose: Option<ContentPurpose>) -> bool {
+        use settings::KeyboardPolicy;
+        match (self.physical_keyboard, self.keyboard_policy) {
+            (Presence::Missing, _) => true,
+            (Presence::Present, KeyboardPolicy::Show) => true,
+            (Presence::Present, KeyboardPolicy::Hide) => false,
+            (Presence::Present, KeyboardPolicy::Numeric) => match purpose {
+                Some(ContentPurpose::Digits)
+                | Some(ContentPurpose::Number)
+                | Some(ContentPurpose::Phone)
+                | Some(ContentPurpose::Pin) => true,
+                _ => false,
+            },
+        }
+    }
+
+    /// Picks the output according to the policy.
+    /// Stays on the current output as long as it qualifies,
+    /// so that the panel doesn't jump around when outputs come and go.
+    fn choose_output(&self) -> Option<OutputId> {
+        use settings::OutputPolicy;
+
+        let matching = |f: &dyn Fn(&OutputState) -> bool| {
+            let mut found: Vec<(&OutputId, &OutputState)> = self.outputs.iter()
+                .filter(|(_id, output)| f(output))
+                .collect();
+            // Hash order is random, names are not.
+            found.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+            found.into_iter().map(|(id, _)| *id).collect::<Vec<_>>()
+        };
+        let named = |name: &str| matching(&|output| output.name.as_deref() == Some(name));
+        let touch = || matching(&|output| output.is_builtin());
+        let any = || matching(&|_| true);
+        let or_else = |found: Vec<OutputId>, fallback: &dyn Fn() -> Vec<OutputId>| {
+            if found.is_empty() { fallback() }
+            else { found }
         };
 
-        if state.debug_mode_enabled {
-            println!(
-                "State is now:
-{:#?}
-Outcome:
-{:#?}",
-                state,
-                state.get_outcome(now),
-            );
+        let candidates = match &self.output_policy {
+            OutputPolicy::Any => any(),
+            // Without a touch screen, there's nothing to type on.
+            OutputPolicy::Touch => touch(),
+            OutputPolicy::Focus => {
+                let focused = self.focused_output_name.as_deref()
+                    .map(named)
+                    .unwrap_or_default();
+                or_else(focused, &touch)
+            },
+            OutputPolicy::Name(name) => or_else(named(name), &any),
+        };
+
+        match self.preferred_output {
+            Some(current) if candidates.contains(&current) => Some(current),
+            _ => candidates.first().copied(),
         }
-        state
     }
 
-    fn get_preferred_height_and_arrangement(output: &OutputState)
+    /// The fractional scale preferred by the compositor wins
+    /// over the integer scale of the output.
+    fn get_scale(output: &OutputState, preferred: Option<panel::Scale>) -> panel::Scale {
+        preferred.unwrap_or(panel::Scale::from_integer(output.scale as u32))
+    }
+
+    /// `panel_width` is the width allocated to the panel by the compositor,
+    /// if known, in scaled pixels.
+    /// It may be smaller than the output when other panels take up the edges.
+    fn get_preferred_height_and_arrangement(
+        output: &OutputState,
+        preference: &settings::PanelHeight,
+        panel_width: Option<u32>,
+        preferred_scale: Option<panel::Scale>,
+    )
         -> Option<(PixelSize, ArrangementKind)>
     {
+        let scale = Self::get_scale(output, preferred_scale);
         output.get_pixel_size()
             .map(|px_size| {
-                // Assume isotropy.
+                let panel_px_width = panel_width
+                    .map(|width| scale.apply(width))
+                    .filter(|width| *width > 0)
+                    .map(|width| cmp::min(width, px_size.width))
+                    .unwrap_or(px_size.width);
+
                 // Pixels/mm.
-                let density = output.get_physical_size()
-                    .and_then(|size| size.width)
-                    .map(|width| Rational {
-                        numerator: px_size.width as i32,
-                        denominator: width.0 as u32,
-                    })
+                // Pixels are not necessarily square,
+                // and heights are measured vertically.
+                let physical_size = output.get_physical_size();
+                let density_along = |pixels: u32, mm: Option<Millimeter>| mm
+                    .filter(|mm| mm.0 > 0)
+                    .map(|mm| Rational {
+                        numerator: pixels as i32,
+                        denominator: mm.0 as u32,
+                    });
+                let density = physical_size
+                    .and_then(|size| density_along(px_size.height, size.height))
+                    .or_else(|| physical_size
+                        .and_then(|size| density_along(px_size.width, size.width))
+                    )
                     // Whatever the Librem 5 has,
                     // as a good default.
                     .unwrap_or(Rational {
@@ -399,30 +721,27 @@ Outcome:
                         denominator: 65,
                     });
 
-                // Based on what works on the L5.
-                // Exceeding that probably wastes space. Reducing makes typing harder.
-                const IDEAL_TARGET_SIZE: Rational<Millimeter> = Rational {
-                    numerator: Millimeter(948),
-                    denominator: 100,
-                };
-
-                // TODO: calculate based on selected layout
-                const ROW_COUNT: u32 = 4;
-
-                let ideal_height = IDEAL_TARGET_SIZE * ROW_COUNT as i32;
-                let ideal_height_px = (ideal_height * density).ceil().0 as u32;
-
                 // Reduce height to match what the layout can fill.
-                // For this, we need to guess if normal or wide will be picked up.
-                // This must match `eek_gtk_keyboard.c::get_type`.
+                // The arrangement chosen here is the one the layout gets loaded with.
                 // TODO: query layout database and choose one directly
                 let abstract_width
                     = PixelSize {
-                        scale_factor: output.scale as u32,
-                        pixels: px_size.width,
+                        scale_factor: scale,
+                        pixels: panel_px_width,
                     } 
                     .as_scaled_ceiling();
 
+                // Tablets in landscape are too wide to reach the middle
+                // with thumbs.
+                let is_split = px_size.width > px_size.height
+                    && output.get_physical_size()
+                        .and_then(|size| size.width)
+                        .map(|width| {
+                            width.0 >= SPLIT_MIN_WIDTH.0
+                                && width.0 <= SPLIT_MAX_WIDTH.0
+                        })
+                        .unwrap_or(false);
+
                 let (arrangement, height_as_widths) = {
                     if abstract_width < 540 {(
                         ArrangementKind::Base,
@@ -431,7 +750,10 @@ Outcome:
                             denominator: 360,
                         },
                     )} else {(
-                        ArrangementKind::Wide,
+                        match is_split {
+                            true => ArrangementKind::Split,
+                            false => ArrangementKind::Wide,
+                        },
                         Rational {
                             numerator: 172,
                             denominator: 540,
@@ -439,22 +761,74 @@ Outcome:
                     )}
                 };
 
-                let height
-                    = cmp::min(
-                        ideal_height_px,
-                        (height_as_widths * px_size.width as i32).ceil() as u32,
-                    );
+                let preference = match px_size.width > px_size.height {
+                    true => preference.landscape,
+                    false => preference.portrait,
+                };
+
+                let height = match preference {
+                    settings::Height::Auto => {
+                        // Based on what works on the L5.
+                        // Exceeding that probably wastes space. Reducing makes typing harder.
+                        const IDEAL_TARGET_SIZE: Rational<Millimeter> = Rational {
+                            numerator: Millimeter(948),
+                            denominator: 100,
+                        };
+
+                        // TODO: calculate based on selected layout
+                        const ROW_COUNT: u32 = 4;
+
+                        let ideal_height = IDEAL_TARGET_SIZE * ROW_COUNT as i32;
+                        let ideal_height_px = (ideal_height * density).ceil().0 as u32;
+
+                        cmp::min(
+                            cmp::min(
+                                ideal_height_px,
+                                (height_as_widths * panel_px_width as i32).ceil() as u32,
+                            ),
+                            px_size.height / 2,
+                        )
+                    },
+                    settings::Height::Millimeter(mm) => {
+                        let height = Rational {
+                            numerator: Millimeter(mm as i32),
+                            denominator: 1,
+                        };
+                        (height * density).ceil().0 as u32
+                    },
+                    settings::Height::Percent(percent)
+                        => px_size.height * percent / 100,
+                };
 
                 (
                     PixelSize {
-                        scale_factor: output.scale as u32,
-                        pixels: cmp::min(height, px_size.height / 2),
+                        scale_factor: scale,
+                        pixels: cmp::min(height, px_size.height),
                     },
                     arrangement,
                 )
             })
     }
     
+    fn get_placement(
+        output: &OutputState,
+        floating: &settings::Floating,
+        preferred_scale: Option<panel::Scale>,
+    )
+        -> animation::Placement
+    {
+        match (floating.enabled, output.get_pixel_size()) {
+            (true, Some(px_size)) => animation::Placement::Floating {
+                width: PixelSize {
+                    scale_factor: Self::get_scale(output, preferred_scale),
+                    pixels: (px_size.width as f64 * floating.width).round() as u32,
+                },
+                position: panel::Position { x: floating.x, y: floating.y },
+            },
+            _ => animation::Placement::Docked,
+        }
+    }
+
     /// Returns layout name, overlay name
     fn get_layout_names(&self) -> (String, Option<String>) {
         (
@@ -479,23 +853,43 @@ impl ActorState for Application {
     }
     
     fn get_outcome(&self, now: Instant) -> Outcome {
-        // FIXME: include physical keyboard presence
         Outcome {
             panel: match self.preferred_output {
                 None => animation::Outcome::Hidden,
                 Some(output) => {
-                    let (height, arrangement) = Self::get_preferred_height_and_arrangement(self.outputs.get(&output).unwrap())
+                    let output_state = self.outputs.get(&output).unwrap();
+                    let panel_width = match &self.panel_allocation {
+                        Some((allocated_output, size)) if *allocated_output == output
+                            => Some(size.width),
+                        _ => None,
+                    };
+                    let scale = match &self.panel_scale {
+                        Some((scaled_output, scale)) if *scaled_output == output
+                            => Some(*scale),
+                        _ => None,
+                    };
+                    let (height, arrangement) = Self::get_preferred_height_and_arrangement(
+                        output_state,
+                        &self.panel_height,
+                        panel_width,
+                        scale,
+                    )
                         .unwrap_or((
-                            PixelSize{pixels: 0, scale_factor: 1},
+                            PixelSize{pixels: 0, scale_factor: panel::Scale::from_integer(1)},
                             ArrangementKind::Base,
                         ));
                     let (layout_name, overlay) = self.get_layout_names();
         
                     // TODO: Instead of setting size to 0 when the output is invalid,
                     // simply go invisible.
-                    let visible = animation::Outcome::Visible{
+                    let visible = |slid_away: f64| animation::Outcome::Visible{
                         output,
                         height,
+                        offset: PixelSize {
+                            pixels: (height.pixels as f64 * slid_away).round() as u32,
+                            scale_factor: height.scale_factor,
+                        },
+                        placement: Self::get_placement(output_state, &self.floating, scale),
                         contents: animation::Contents {
                             kind: arrangement,
                             name: layout_name,
@@ -507,27 +901,35 @@ impl ActorState for Application {
                         }
                     };
 
-                    match (self.physical_keyboard, self.visibility_override) {
-                        (_, visibility::State::ForcedHidden) => animation::Outcome::Hidden,
-                        (_, visibility::State::ForcedVisible) => visible,
-                        (Presence::Present, visibility::State::NotForced) => animation::Outcome::Hidden,
-                        (Presence::Missing, visibility::State::NotForced) => match self.im {
-                            InputMethod::Active(_) => visible,
-                            InputMethod::InactiveSince(since) => {
-                                if now < since + animation::HIDING_TIMEOUT { visible }
-                                else { animation::Outcome::Hidden }
-                            },
-                        },
+                    let wanted_visible = self.is_wanted_visible(now);
+                    let transition = match wanted_visible == self.panel_transition.visible {
+                        true => self.panel_transition,
+                        // The hiding timeout passed,
+                        // but its event didn't arrive yet to start the slide.
+                        false => animation::Transition { visible: wanted_visible, since: now },
+                    };
+                    let progress = transition.get_progress(now, self.slide_duration);
+
+                    match (wanted_visible, progress < 1.0) {
+                        (true, _) => visible(1.0 - progress),
+                        (false, true) => visible(progress),
+                        (false, false) => animation::Outcome::Hidden,
                     }
                 }
             },
             im: self.im.clone(),
+            view: self.view_choice.clone(),
         }
     }
 
     /// Returns the next time to update the outcome.
     fn get_next_wake(&self, now: Instant) -> Option<Instant> {
-        match self {
+        let slide_end = self.panel_transition.since + self.slide_duration;
+        let slide_step = match now < slide_end {
+            true => Some(cmp::min(now + animation::FRAME_INTERVAL, slide_end)),
+            false => None,
+        };
+        let hiding = match self {
             Self {
                 visibility_override: visibility::State::NotForced,
                 im: InputMethod::InactiveSince(since),
@@ -538,6 +940,10 @@ impl ActorState for Application {
                 else { None }
             }
             _ => None,
+        };
+        match (hiding, slide_step) {
+            (Some(hiding), Some(step)) => Some(cmp::min(hiding, step)),
+            (hiding, step) => hiding.or(step),
         }
     }
 }
@@ -719,6 +1125,7 @@ pub mod test {
         let state = Application {
             im: InputMethod::Active(imdetails_new()),
             physical_keyboard: Presence::Missing,
+            keyboard_policy: settings::KeyboardPolicy::Hide,
             visibility_override: visibility::State::NotForced,
             ..application_with_fake_output(start)
         };
@@ -756,32 +1163,676 @@ pub mod test {
 
     }
 
+    #[test]
+    fn keyboard_policy_numeric() {
+        let now = Instant::now();
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            physical_keyboard: Presence::Present,
+            keyboard_policy: settings::KeyboardPolicy::Numeric,
+            ..application_with_fake_output(now)
+        };
+        assert_eq!(state.get_outcome(now).panel, animation::Outcome::Hidden);
+
+        let state = state.apply_event(
+            Event::InputMethod(InputMethod::Active(InputMethodDetails {
+                purpose: ContentPurpose::Pin,
+                hint: ContentHint::NONE,
+            })),
+            now,
+        );
+        assert_matches!(state.get_outcome(now).panel, animation::Outcome::Visible{..});
+
+        let state = state.apply_event(
+            Event::KeyboardPolicy(settings::KeyboardPolicy::Hide),
+            now,
+        );
+        assert_eq!(state.get_outcome(now).panel, animation::Outcome::Hidden);
+
+        let state = state.apply_event(
+            Event::KeyboardPolicy(settings::KeyboardPolicy::Show),
+            now,
+        );
+        assert_matches!(state.get_outcome(now).panel, animation::Outcome::Visible{..});
+    }
+
+    /// Built-in laptop keyboards are always present,
+    /// so they don't hide the panel unless asked to.
+    #[test]
+    fn keyboard_present_shown_by_default() {
+        let now = Instant::now();
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let state = state.apply_event(Event::PhysicalKeyboard(Presence::Present), now);
+        assert_matches!(state.get_outcome(now).panel, animation::Outcome::Visible{..});
+    }
+
+    /// The accessibility setting wins over keyboard detection.
+    #[test]
+    fn screen_keyboard_disabled() {
+        let now = Instant::now();
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            physical_keyboard: Presence::Missing,
+            ..application_with_fake_output(now)
+        };
+        let state = state.apply_event(Event::ScreenKeyboardEnabled(false), now);
+        assert_eq!(state.get_outcome(now).panel, animation::Outcome::Hidden);
+
+        let state = state.apply_event(Event::ScreenKeyboardEnabled(true), now);
+        assert_matches!(state.get_outcome(now).panel, animation::Outcome::Visible{..});
+    }
+
+    #[test]
+    fn fake_output_removed() {
+        let now = Instant::now();
+        let mut state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        for output in state.outputs.values_mut() {
+            output.name = Some("DSI-1".into());
+        }
+
+        let state = state.apply_event(
+            Event::Debug(debug::Event::FakeOutput {
+                name: "HDMI-1".into(),
+                state: None,
+            }),
+            now,
+        );
+        assert_matches!(
+            state.get_outcome(now).panel,
+            animation::Outcome::Visible{..}
+        );
+
+        let state = state.apply_event(
+            Event::Debug(debug::Event::FakeOutput {
+                name: "DSI-1".into(),
+                state: None,
+            }),
+            now,
+        );
+        assert_matches!(
+            state.get_outcome(now).panel,
+            animation::Outcome::Hidden
+        );
+    }
+
+    #[test]
+    fn fake_output_created() {
+        let now = Instant::now();
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let fake = OutputState {
+            current_mode: Some(outputs::Mode { width: 1920, height: 1080 }),
+            geometry: None,
+            scale: 1,
+            name: Some("HDMI-1".into()),
+        };
+        let state = state.apply_event(
+            Event::Debug(debug::Event::FakeOutput {
+                name: "HDMI-1".into(),
+                state: Some(fake),
+            }),
+            now,
+        );
+        assert_eq!(state.outputs.len(), 2);
+        let (id, output) = state.outputs.iter()
+            .find(|(_id, output)| output.name.as_deref() == Some("HDMI-1"))
+            .unwrap();
+        assert!(id.is_synthetic());
+        assert_eq!(output.current_mode.map(|mode| mode.width), Some(1920));
+    }
+
+    #[test]
+    fn output_policy() {
+        let now = Instant::now();
+        let named_output = |name: &str| OutputState {
+            current_mode: None,
+            geometry: None,
+            scale: 1,
+            name: Some(name.into()),
+        };
+        let builtin = fake_output_id(1);
+        let external = fake_output_id(2);
+        let state = Application {
+            outputs: [
+                (builtin, named_output("DSI-1")),
+                (external, named_output("HDMI-A-1")),
+            ].iter().cloned().collect(),
+            preferred_output: Some(external),
+            ..Application::new(now)
+        };
+
+        let state = state.apply_event(
+            Event::OutputPolicy(settings::OutputPolicy::Touch),
+            now,
+        );
+        assert_eq!(state.preferred_output, Some(builtin));
+
+        let state = state.apply_event(
+            Event::OutputPolicy(settings::OutputPolicy::Name("HDMI-A-1".into())),
+            now,
+        );
+        assert_eq!(state.preferred_output, Some(external));
+
+        let state = state.apply_event(
+            Event::OutputPolicy(settings::OutputPolicy::Focus),
+            now,
+        );
+        // Nothing focused yet
+        assert_eq!(state.preferred_output, Some(builtin));
+
+        let state = state.apply_event(
+            Event::FocusedOutput(Some("HDMI-A-1".into())),
+            now,
+        );
+        assert_eq!(state.preferred_output, Some(external));
+
+        let state = state.apply_event(
+            Event::Output(outputs::Event {
+                output: external,
+                change: outputs::ChangeType::Removed,
+            }),
+            now,
+        );
+        assert_eq!(state.preferred_output, Some(builtin));
+    }
+
+    #[test]
+    fn output_policy_no_touch() {
+        let now = Instant::now();
+        let external = fake_output_id(2);
+        let state = Application {
+            outputs: [(
+                external,
+                OutputState {
+                    current_mode: None,
+                    geometry: None,
+                    scale: 1,
+                    name: Some("HDMI-A-1".into()),
+                },
+            )].iter().cloned().collect(),
+            preferred_output: Some(external),
+            ..Application::new(now)
+        };
+        let state = state.apply_event(
+            Event::OutputPolicy(settings::OutputPolicy::Touch),
+            now,
+        );
+        assert_eq!(state.preferred_output, None);
+    }
+
+    fn l5_output() -> OutputState {
+        use crate::outputs::{Mode, Geometry, c, Size};
+        OutputState {
+            current_mode: Some(Mode {
+                width: 720,
+                height: 1440,
+            }),
+            geometry: Some(Geometry{
+                transform: c::Transform::Normal,
+                phys_size: Size {
+                    width: Some(Millimeter(65)),
+                    height: Some(Millimeter(130)),
+                },
+            }),
+            scale: 2,
+            name: None,
+        }
+    }
+
     #[test]
     fn size_l5() {
+        assert_eq!(
+            Application::get_preferred_height_and_arrangement(
+                &l5_output(),
+                &Default::default(),
+                None,
+                None,
+            ),
+            Some((
+                PixelSize {
+                    scale_factor: panel::Scale::from_integer(2),
+                    pixels: 420,
+                },
+                ArrangementKind::Base,
+            )),
+        );
+    }
+
+    #[test]
+    fn split_tablet() {
         use crate::outputs::{Mode, Geometry, c, Size};
+        let tablet = OutputState {
+            current_mode: Some(Mode {
+                width: 1920,
+                height: 1200,
+            }),
+            geometry: Some(Geometry{
+                transform: c::Transform::Normal,
+                phys_size: Size {
+                    width: Some(Millimeter(217)),
+                    height: Some(Millimeter(136)),
+                },
+            }),
+            scale: 1,
+            name: None,
+        };
+        assert_matches!(
+            Application::get_preferred_height_and_arrangement(
+                &tablet,
+                &Default::default(),
+                None,
+                None,
+            ),
+            Some((_, ArrangementKind::Split))
+        );
+
+        let portrait = OutputState {
+            geometry: tablet.geometry.map(|geometry| Geometry {
+                transform: c::Transform::Rotated90,
+                ..geometry
+            }),
+            ..tablet
+        };
+        assert_matches!(
+            Application::get_preferred_height_and_arrangement(
+                &portrait,
+                &Default::default(),
+                None,
+                None,
+            ),
+            Some((_, ArrangementKind::Wide))
+        );
+
+        let monitor = OutputState {
+            current_mode: Some(Mode {
+                width: 2560,
+                height: 1440,
+            }),
+            geometry: Some(Geometry{
+                transform: c::Transform::Normal,
+                phys_size: Size {
+                    width: Some(Millimeter(597)),
+                    height: Some(Millimeter(336)),
+                },
+            }),
+            scale: 1,
+            name: None,
+        };
+        assert_matches!(
+            Application::get_preferred_height_and_arrangement(
+                &monitor,
+                &Default::default(),
+                None,
+                None,
+            ),
+            Some((_, ArrangementKind::Wide))
+        );
+    }
+
+    #[test]
+    fn size_l5_configured() {
+        let preference = settings::PanelHeight {
+            portrait: settings::Height::Millimeter(40),
+            landscape: settings::Height::Percent(30),
+        };
         assert_eq!(
-            Application::get_preferred_height_and_arrangement(&OutputState {
-                current_mode: Some(Mode {
-                    width: 720,
-                    height: 1440,
-                }),
-                geometry: Some(Geometry{
-                    transform: c::Transform::Normal,
-                    phys_size: Size {
-                        width: Some(Millimeter(65)),
-                        height: Some(Millimeter(130)),
-                    },
-                }),
-                scale: 2,
-                name: None,
+            Application::get_preferred_height_and_arrangement(
+                &l5_output(),
+                &preference,
+                None,
+                None,
+            ),
+            Some((
+                PixelSize {
+                    scale_factor: panel::Scale::from_integer(2),
+                    pixels: 444,
+                },
+                ArrangementKind::Base,
+            )),
+        );
+
+        let rotated = OutputState {
+            geometry: l5_output().geometry.map(|geometry| outputs::Geometry {
+                transform: outputs::c::Transform::Rotated90,
+                ..geometry
+            }),
+            ..l5_output()
+        };
+        assert_eq!(
+            Application::get_preferred_height_and_arrangement(
+                &rotated,
+                &preference,
+                None,
+                None,
+            ),
+            Some((
+                PixelSize {
+                    scale_factor: panel::Scale::from_integer(2),
+                    pixels: 216,
+                },
+                ArrangementKind::Wide,
+            )),
+        );
+    }
+
+    #[test]
+    fn size_rotated_physical_size_reported_rotated() {
+        use crate::outputs::{Geometry, Size};
+        let preference = settings::PanelHeight {
+            portrait: settings::Height::Auto,
+            landscape: settings::Height::Millimeter(20),
+        };
+        let rotated = OutputState {
+            geometry: l5_output().geometry.map(|geometry| Geometry {
+                transform: outputs::c::Transform::Rotated90,
+                ..geometry
+            }),
+            ..l5_output()
+        };
+        let reported_rotated = OutputState {
+            geometry: Some(Geometry {
+                transform: outputs::c::Transform::Rotated90,
+                phys_size: Size {
+                    width: Some(Millimeter(130)),
+                    height: Some(Millimeter(65)),
+                },
             }),
+            ..l5_output()
+        };
+        let expected = Some((
+            PixelSize {
+                scale_factor: panel::Scale::from_integer(2),
+                pixels: 222,
+            },
+            ArrangementKind::Wide,
+        ));
+        assert_eq!(
+            Application::get_preferred_height_and_arrangement(
+                &rotated,
+                &preference,
+                None,
+                None,
+            ),
+            expected,
+        );
+        assert_eq!(
+            Application::get_preferred_height_and_arrangement(
+                &reported_rotated,
+                &preference,
+                None,
+                None,
+            ),
+            expected,
+        );
+    }
+
+    #[test]
+    fn size_fractional_scale() {
+        let size = Application::get_preferred_height_and_arrangement(
+            &l5_output(),
+            &Default::default(),
+            None,
+            Some(panel::Scale(180)),
+        );
+        assert_eq!(
+            size,
             Some((
                 PixelSize {
-                    scale_factor: 2,
+                    scale_factor: panel::Scale(180),
                     pixels: 420,
                 },
                 ArrangementKind::Base,
             )),
         );
+        assert_eq!(size.unwrap().0.as_scaled_ceiling(), 280);
+    }
+
+    #[test]
+    fn panel_height_applied_live() {
+        let now = Instant::now();
+        let mut state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let id = state.preferred_output.unwrap();
+        state.outputs.insert(id, l5_output());
+
+        let state = state.apply_event(
+            Event::PanelHeight(settings::PanelHeight {
+                portrait: settings::Height::Percent(50),
+                landscape: settings::Height::Auto,
+            }),
+            now,
+        );
+        assert_matches!(
+            state.get_outcome(now).panel,
+            animation::Outcome::Visible {
+                height: PixelSize { pixels: 720, .. },
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn floating_placement() {
+        use crate::event_loop::Outcome as _;
+        let now = Instant::now();
+        let mut state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let id = state.preferred_output.unwrap();
+        state.outputs.insert(id, l5_output());
+        let docked = state.get_outcome(now);
+
+        let state = state.apply_event(
+            Event::Floating(settings::Floating {
+                enabled: true,
+                x: 10,
+                y: 20,
+                width: 0.5,
+            }),
+            now,
+        );
+        let floating = state.get_outcome(now);
+        assert_matches!(
+            floating.panel,
+            animation::Outcome::Visible {
+                placement: animation::Placement::Floating {
+                    width: PixelSize { pixels: 360, scale_factor: panel::Scale(240) },
+                    position: panel::Position { x: 10, y: 20 },
+                },
+                ..
+            }
+        );
+        assert_matches!(
+            docked.get_commands_to_reach(&floating).panel_visibility,
+            Some(panel::Command::ShowFloating { .. })
+        );
+    }
+
+    /// A view chosen from outside survives a layout rebuild until it's shown,
+    /// but not a change of layout.
+    #[test]
+    fn view_choice_pending() {
+        use crate::event_loop::Outcome as _;
+        let now = Instant::now();
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let before = state.get_outcome(now);
+        let state = state.apply_event(Event::ViewChoice("numbers".into()), now);
+        let chosen = state.get_outcome(now);
+        assert_matches!(
+            before.get_commands_to_reach(&chosen).view_selection,
+            Some(ViewChoice { ref name, .. }) if name == "numbers"
+        );
+        assert_eq!(chosen.get_commands_to_reach(&chosen).view_selection, None);
+
+        // A different purpose rebuilds the layout.
+        let state = state.apply_event(
+            Event::InputMethod(InputMethod::Active(InputMethodDetails {
+                purpose: ContentPurpose::Email,
+                ..imdetails_new()
+            })),
+            now,
+        );
+        let rebuilt = state.get_outcome(now);
+        let commands = chosen.get_commands_to_reach(&rebuilt);
+        assert!(commands.layout_selection.is_some());
+        assert_matches!(
+            commands.view_selection,
+            Some(ViewChoice { ref name, .. }) if name == "numbers"
+        );
+
+        let state = state.apply_event(
+            Event::LayoutChoice(LayoutChoice {
+                name: "de".into(),
+                source: LayoutSource::Xkb,
+            }),
+            now,
+        );
+        let other = state.get_outcome(now);
+        assert_eq!(rebuilt.get_commands_to_reach(&other).view_selection, None);
+    }
+
+    /// Once shown, the requested view doesn't come back on its own.
+    #[test]
+    fn view_choice_applied() {
+        use crate::event_loop::Outcome as _;
+        let start = Instant::now();
+        let mut now = start;
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let state = state.apply_event(Event::ViewChoice("numbers".into()), now);
+        let serial = match state.get_outcome(now).view {
+            Some(ViewChoice { serial, .. }) => serial,
+            None => panic!("View not chosen"),
+        };
+        let state = state.apply_event(Event::ViewApplied(serial), now);
+        let shown = state.get_outcome(now);
+        assert_eq!(shown.view, None);
+
+        let state = state.apply_event(Event::InputMethod(InputMethod::InactiveSince(now)), now);
+        now += Duration::from_secs(3);
+        let hidden = state.get_outcome(now);
+        assert_eq!(hidden.panel, animation::Outcome::Hidden);
+
+        let state = state.apply_event(Event::InputMethod(InputMethod::Active(imdetails_new())), now);
+        let reshown = state.get_outcome(now);
+        let commands = hidden.get_commands_to_reach(&reshown);
+        assert!(commands.layout_selection.is_some());
+        assert_eq!(commands.view_selection, None);
+
+        // A late confirmation doesn't drop a newer request.
+        let state = state.apply_event(Event::ViewChoice("symbols".into()), now);
+        let state = state.apply_event(Event::ViewApplied(serial), now);
+        assert_matches!(
+            state.get_outcome(now).view,
+            Some(ViewChoice { ref name, .. }) if name == "symbols"
+        );
+    }
+
+    #[test]
+    fn panel_allocation_narrows() {
+        let now = Instant::now();
+        let mut state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let id = state.preferred_output.unwrap();
+        state.outputs.insert(id, l5_output());
+
+        let state = state.apply_event(
+            Event::PanelConfigured {
+                output: id,
+                size: panel::Size { width: 300, height: 210 },
+            },
+            now,
+        );
+        assert_matches!(
+            state.get_outcome(now).panel,
+            animation::Outcome::Visible {
+                height: PixelSize { pixels: 350, .. },
+                ..
+            }
+        );
+
+        // The output changes, so the allocation is stale.
+        let state = state.apply_event(
+            Event::Output(outputs::Event {
+                output: id,
+                change: outputs::ChangeType::Altered(l5_output()),
+            }),
+            now,
+        );
+        assert_matches!(
+            state.get_outcome(now).panel,
+            animation::Outcome::Visible {
+                height: PixelSize { pixels: 420, .. },
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn slide() {
+        let start = Instant::now();
+        let mut state = Application {
+            slide_duration: Duration::from_millis(100),
+            ..application_with_fake_output(start)
+        };
+        let id = state.preferred_output.unwrap();
+        state.outputs.insert(id, l5_output());
+        let offset = |state: &Application, now| match state.get_outcome(now).panel {
+            animation::Outcome::Visible { offset, .. } => Some(offset.pixels),
+            animation::Outcome::Hidden => None,
+        };
+
+        let now = start + Duration::from_secs(1);
+        let state = state.apply_event(Event::InputMethod(InputMethod::Active(imdetails_new())), now);
+        assert_eq!(offset(&state, now), Some(420));
+        assert_eq!(offset(&state, now + Duration::from_millis(50)), Some(210));
+        assert_eq!(offset(&state, now + Duration::from_millis(100)), Some(0));
+        assert_eq!(
+            state.get_next_wake(now),
+            Some(now + animation::FRAME_INTERVAL),
+        );
+
+        let now = now + Duration::from_secs(1);
+        let state = state.apply_event(Event::InputMethod(InputMethod::InactiveSince(now)), now);
+        // Anti-flicker delay
+        assert_eq!(offset(&state, now), Some(0));
+
+        let now = now + animation::HIDING_TIMEOUT;
+        let state = state.apply_event(Event::TimeoutReached(now), now);
+        assert_eq!(offset(&state, now + Duration::from_millis(50)), Some(210));
+        assert_eq!(offset(&state, now + Duration::from_millis(100)), None);
+    }
+
+    #[test]
+    fn slide_reduced_motion() {
+        let now = Instant::now();
+        let state = Application {
+            im: InputMethod::Active(imdetails_new()),
+            ..application_with_fake_output(now)
+        };
+        let state = state.apply_event(Event::SlideDuration(Duration::from_millis(0)), now);
+        assert_matches!(
+            state.get_outcome(now).panel,
+            animation::Outcome::Visible { offset: PixelSize { pixels: 0, .. }, .. }
+        );
+        assert_eq!(state.get_next_wake(now), None);
     }
 }
@@ -18,10 +18,16 @@
 
 /*! CSS data loading. */
 
+use std::cell::{ Cell, RefCell };
 use std::env;
+use std::path::{ Path, PathBuf };
 use crate::logging;
+use crate::settings;
+use crate::xdg;
 
+use gio::prelude::{ FileExt, FileMonitorExt };
 use glib::prelude::ObjectExt;
+use gtk::prelude::{ CssProviderExt, WidgetExt };
 use crate::logging::Warn;
 
 /// Gathers stuff defined in C or called by C
@@ -31,7 +37,6 @@ pub mod c {
     use gtk;
     use gtk_sys;
     
-    use gtk::prelude::CssProviderExt;
     use glib::translate::ToGlibPtr;
 
     /// Loads the layout style based on current theme
@@ -46,18 +51,36 @@ pub mod c {
         #[cfg(not(feature = "glib_v0_14"))]
         let theme = gtk::Settings::get_default();
         
-        let theme = theme.map(|settings| get_theme_name(&settings));
-        
-        let css_name = path_from_theme(theme);
+        let scheme = COLOR_SCHEME.with(|scheme| scheme.get());
+        let theme = theme.map(|settings| get_theme_name(&settings, scheme));
+        let is_dark = matches!(
+            &theme,
+            Some(GtkTheme { variant: Some(variant), .. }) if variant == "dark"
+        );
+
+        let mut candidates = Vec::new();
+        if ACCESSIBILITY.with(|a11y| a11y.get()).high_contrast {
+            candidates.push(path_from_theme(Some(GtkTheme {
+                name: "HighContrast".into(),
+                variant: None,
+            })));
+        }
+        candidates.push(path_from_theme(theme));
+        // Themes without a squeekboard style of their own still get a dark one
+        if is_dark {
+            candidates.push(path_from_theme(Some(GtkTheme {
+                name: "Adwaita".into(),
+                variant: Some("dark".into()),
+            })));
+        }
 
-        let resource_name = if gio::resources_get_info(
-            &css_name,
-            gio::ResourceLookupFlags::NONE
-        ).is_ok() {
-            css_name
-        } else { // use default if this path doesn't exist
-            path_from_theme(None)
-        };
+        let resource_name = candidates.into_iter()
+            .find(|name| gio::resources_get_info(
+                name,
+                gio::ResourceLookupFlags::NONE,
+            ).is_ok())
+            // use default if none of the paths exist
+            .unwrap_or_else(|| path_from_theme(None));
 
         let provider = gtk::CssProvider::new();
         provider.load_from_resource(&resource_name);
@@ -65,6 +88,159 @@ pub mod c {
     }
 }
 
+/// The user's own stylesheet, layered over the built-in one
+fn user_style_path() -> Option<PathBuf> {
+    xdg::config_path("squeekboard/style.css")
+}
+
+thread_local! {
+    /// The provider gets reloaded in place,
+    /// so it stays attached to the screen for the program lifetime.
+    /// The monitor stops watching when dropped.
+    static USER_STYLE: RefCell<Option<(gtk::CssProvider, Option<gio::FileMonitor>)>>
+        = RefCell::new(None);
+}
+
+/// A missing file is not an error, it just means no overrides.
+fn load_user_style(provider: &gtk::CssProvider, path: &Path) {
+    let loaded = match path.exists() {
+        true => provider.load_from_path(&path.to_string_lossy()),
+        false => provider.load_from_data(b""),
+    };
+    loaded.or_print(
+        logging::Problem::Warning,
+        &format!("Can't load style from {}", path.display()),
+    );
+    for window in gtk::Window::list_toplevels() {
+        window.queue_draw();
+    }
+}
+
+/// Applies the user stylesheet and reloads it whenever it changes.
+/// Must be called on the UI thread, after GTK is initialized.
+pub fn init_user_style() {
+    let path = match user_style_path() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let provider = gtk::CssProvider::new();
+    load_user_style(&provider, &path);
+
+    #[cfg(feature = "glib_v0_14")]
+    let screen = gdk::Screen::default();
+    #[cfg(not(feature = "glib_v0_14"))]
+    let screen = gdk::Screen::get_default();
+
+    match screen {
+        Some(screen) => gtk::StyleContext::add_provider_for_screen(
+            &screen,
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_USER,
+        ),
+        None => log_print!(
+            logging::Level::Surprise,
+            "No screen to apply {} to", path.display(),
+        ),
+    };
+
+    #[cfg(feature = "glib_v0_14")]
+    let file = gio::File::for_path(&path);
+    #[cfg(not(feature = "glib_v0_14"))]
+    let file = gio::File::new_for_path(&path);
+
+    let monitor = file.monitor_file(
+        gio::FileMonitorFlags::NONE,
+        None::<&gio::Cancellable>,
+    )
+        .or_print(
+            logging::Problem::Warning,
+            &format!("Can't watch {} for changes", path.display()),
+        );
+
+    if let Some(monitor) = &monitor {
+        let provider = provider.clone();
+        monitor.connect_changed(move |_monitor, _file, _other, event| {
+            match event {
+                gio::FileMonitorEvent::ChangesDoneHint
+                | gio::FileMonitorEvent::Created
+                | gio::FileMonitorEvent::Deleted
+                    => load_user_style(&provider, &path),
+                _ => {},
+            }
+        });
+    }
+    USER_STYLE.with(|style| *style.borrow_mut() = Some((provider, monitor)));
+}
+
+/// The freedesktop appearance preference
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorScheme {
+    /// Leaves the choice to the GTK theme
+    NoPreference,
+    PreferDark,
+    PreferLight,
+}
+
+impl ColorScheme {
+    /// Takes the value of `org.freedesktop.appearance color-scheme`.
+    /// Unknown values count as no preference, as the spec requires.
+    pub fn from_portal(value: u32) -> Self {
+        match value {
+            1 => ColorScheme::PreferDark,
+            2 => ColorScheme::PreferLight,
+            _ => ColorScheme::NoPreference,
+        }
+    }
+}
+
+thread_local! {
+    static COLOR_SCHEME: Cell<ColorScheme> = Cell::new(ColorScheme::NoPreference);
+    static ACCESSIBILITY: Cell<settings::Accessibility>
+        = Cell::new(Default::default());
+}
+
+/// Makes renderers pick the style again and redraw.
+fn reload_theme() {
+    #[cfg(feature = "glib_v0_14")]
+    let settings = gtk::Settings::default();
+    #[cfg(not(feature = "glib_v0_14"))]
+    let settings = gtk::Settings::get_default();
+    // Renderers reload the style whenever the theme changes.
+    if let Some(settings) = settings {
+        settings.notify("gtk-theme-name");
+    }
+    for window in gtk::Window::list_toplevels() {
+        window.queue_draw();
+    }
+}
+
+/// Switches between the light and dark styles.
+/// Must be called on the UI thread.
+pub fn set_color_scheme(scheme: ColorScheme) {
+    let old = COLOR_SCHEME.with(|current| current.replace(scheme));
+    if old != scheme {
+        reload_theme();
+    }
+}
+
+/// Tracks the high contrast and text scaling preferences.
+/// Must be called on the UI thread.
+pub fn init_accessibility() {
+    ACCESSIBILITY.with(|a11y| a11y.set(settings::get_accessibility()));
+    settings::connect_accessibility(|new| {
+        let old = ACCESSIBILITY.with(|a11y| a11y.replace(new));
+        if old != new {
+            reload_theme();
+        }
+    });
+}
+
+/// Multiplier for the font size of labels
+pub fn get_text_scale() -> f64 {
+    ACCESSIBILITY.with(|a11y| a11y.get()).text_scale
+}
+
 // not Adwaita, but rather fall back to default
 const DEFAULT_THEME_NAME: &str = "";
 
@@ -74,8 +250,10 @@ struct GtkTheme {
 }
 
 /// Gets theme as determined by the toolkit
-/// Ported from GTK's gtksettings.c
-fn get_theme_name(settings: &gtk::Settings) -> GtkTheme {
+/// Ported from GTK's gtksettings.c.
+/// A color scheme preference overrides the toolkit's dark preference,
+/// but not the GTK_THEME variable.
+fn get_theme_name(settings: &gtk::Settings, scheme: ColorScheme) -> GtkTheme {
     let env_theme = env::var("GTK_THEME")
         .map(|theme| {
             let mut parts = theme.splitn(2, ":");
@@ -119,14 +297,18 @@ fn get_theme_name(settings: &gtk::Settings) -> GtkTheme {
                     .and_then(|value| check(value.get::<String>()))
                     .unwrap_or(DEFAULT_THEME_NAME.into())
             },
-            variant: {
-                prop(settings, "gtk-application-prefer-dark-theme")
-                    .or_print(logging::Problem::Surprise, "No settings key")
-                    .and_then(|value| check(value.get::<bool>()))
-                    .and_then(|dark_preferred| match dark_preferred {
-                        true => Some("dark".into()),
-                        false => None,
-                    })
+            variant: match scheme {
+                ColorScheme::PreferDark => Some("dark".into()),
+                ColorScheme::PreferLight => None,
+                ColorScheme::NoPreference => {
+                    prop(settings, "gtk-application-prefer-dark-theme")
+                        .or_print(logging::Problem::Surprise, "No settings key")
+                        .and_then(|value| check(value.get::<bool>()))
+                        .and_then(|dark_preferred| match dark_preferred {
+                            true => Some("dark".into()),
+                            false => None,
+                        })
+                },
             },
         },
     }
@@ -11,4 +11,6 @@ struct submission;
 // Defined in Rust
 uint8_t submission_hint_available(struct submission *self);
 void submission_use_layout(struct submission *self, struct squeek_layout *layout, uint32_t time);
+/// Returns 0 if text input is not active
+uint8_t submission_type_string(struct submission *self, const char *text);
 #endif
@@ -20,15 +20,15 @@
 use std::collections::HashSet;
 use std::ffi::CString;
 
-use crate::vkeyboard::c::ZwpVirtualKeyboardV1;
 use crate::action::Modifier;
 use crate::imservice;
-use crate::imservice::IMService;
+use crate::imservice::{ ContentPurpose, InputMethodSink };
 use crate::keyboard::{ KeyCode, KeyStateId, Modifiers, PressType };
 use crate::layout;
+use crate::recording;
+use crate::recording::{ Entry, Recorder, Submitted };
 use crate::util::vec_remove;
-use crate::vkeyboard;
-use crate::vkeyboard::VirtualKeyboard;
+use crate::vkeyboard::VirtualKeyboardSink;
 
 // traits
 use std::iter::FromIterator;
@@ -39,6 +39,9 @@ pub mod c {
 
     use crate::util::c::Wrapped;
 
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
     pub type Submission = Wrapped<super::Submission>;
     
 
@@ -55,6 +58,19 @@ pub mod c {
         submission.use_layout(&layout.shape, Timestamp(time));
     }
 
+    /// Returns 0 if text input is not active
+    #[no_mangle]
+    pub extern "C"
+    fn submission_type_string(
+        submission: Submission,
+        text: *const c_char,
+    ) -> u8 {
+        let submission = submission.clone_ref();
+        let mut submission = submission.borrow_mut();
+        let text = unsafe { CStr::from_ptr(text) };
+        submission.type_string(&text.to_owned()).is_ok() as u8
+    }
+
     #[no_mangle]
     pub extern "C"
     fn submission_hint_available(submission: Submission) -> u8 {
@@ -77,12 +93,14 @@ enum SubmittedAction {
 }
 
 pub struct Submission {
-    imservice: Option<Box<IMService>>,
-    virtual_keyboard: VirtualKeyboard,
+    imservice: Option<Box<dyn InputMethodSink>>,
+    virtual_keyboard: Box<dyn VirtualKeyboardSink>,
     modifiers_active: Vec<(KeyStateId, Modifier)>,
     pressed: Vec<(KeyStateId, SubmittedAction)>,
-    keymap_fds: Vec<vkeyboard::c::KeyMap>,
     keymap_idx: Option<usize>,
+    /// Purpose of the current layout
+    purpose: ContentPurpose,
+    recorder: Option<Recorder>,
 }
 
 pub enum SubmitData<'a> {
@@ -92,17 +110,52 @@ pub enum SubmitData<'a> {
 }
 
 impl Submission {
-    pub fn new(vk: ZwpVirtualKeyboardV1, imservice: Option<Box<IMService>>) -> Self {
+    pub fn new(
+        virtual_keyboard: Box<dyn VirtualKeyboardSink>,
+        imservice: Option<Box<dyn InputMethodSink>>,
+        recorder: Option<Recorder>,
+    ) -> Self {
         Submission {
             imservice,
             modifiers_active: Vec::new(),
-            virtual_keyboard: VirtualKeyboard(vk),
+            virtual_keyboard,
             pressed: Vec::new(),
-            keymap_fds: Vec::new(),
             keymap_idx: None,
+            purpose: ContentPurpose::Normal,
+            recorder,
         }
     }
 
+    /// Typing goes into a field holding secrets.
+    /// The layout is checked in addition to the input method,
+    /// in case the layout got chosen some other way.
+    pub fn is_private(&self) -> bool {
+        self.purpose.is_sensitive()
+            || self.imservice.as_ref()
+                .map(|imservice| imservice.is_sensitive())
+                .unwrap_or(false)
+    }
+
+    /// Records the entry if recording is on,
+    /// unless the user is typing something private.
+    pub fn record(&self, entry: recording::Entry) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record_unless_private(entry, self.is_private());
+        }
+    }
+
+    fn switch_key(&mut self, keycode: &KeyCode, action: PressType, time: Timestamp) {
+        self.select_keymap(keycode.keymap_idx, time);
+        self.record(Entry::Submitted {
+            action: Submitted::Key {
+                keymap: keycode.keymap_idx,
+                code: keycode.code,
+                pressed: action == PressType::Pressed,
+            },
+        });
+        self.virtual_keyboard.switch(keycode.code, action, time);
+    }
+
     /// Sends a submit text event if possible;
     /// otherwise sends key press and makes a note of it
     pub fn handle_press(
@@ -114,16 +167,19 @@ impl Submission {
     ) {
         let mods_are_on = !self.modifiers_active.is_empty();
 
-        let was_committed_as_text = match (&mut self.imservice, mods_are_on) {
+        let committed_text = match (&mut self.imservice, mods_are_on) {
             (Some(imservice), false) => {
                 enum Outcome {
-                    Submitted(Result<(), imservice::SubmitError>),
+                    Submitted(Result<String, imservice::SubmitError>),
                     NotSubmitted,
                 }
 
                 let submit_outcome = match data {
                     SubmitData::Text(text) => {
-                        Outcome::Submitted(imservice.commit_string(text))
+                        Outcome::Submitted(
+                            imservice.commit_string(text)
+                                .map(|()| text.to_string_lossy().into_owned())
+                        )
                     },
                     SubmitData::Erase => {
                         /* Delete_surrounding_text takes byte offsets,
@@ -138,45 +194,37 @@ impl Submission {
 
                 match submit_outcome {
                     Outcome::Submitted(result) => {
-                        match result.and_then(|()| imservice.commit()) {
-                            Ok(()) => true,
-                            Err(imservice::SubmitError::NotActive) => false,
+                        let result = result.and_then(|text| {
+                            imservice.commit().map(|()| text)
+                        });
+                        match result {
+                            Ok(text) => Some(text),
+                            Err(imservice::SubmitError::NotActive) => None,
                         }
                     },
-                    Outcome::NotSubmitted => false,
+                    Outcome::NotSubmitted => None,
                 }
             },
-            (_, _) => false,
+            (_, _) => None,
         };
 
-        let submit_action = match was_committed_as_text {
-            true => SubmittedAction::IMService,
-            false => {
+        let submit_action = match committed_text {
+            Some(text) => {
+                self.record(Entry::Submitted { action: Submitted::Text(text) });
+                SubmittedAction::IMService
+            },
+            None => {
                 let keycodes_count = keycodes.len();
                 for keycode in keycodes.iter() {
-                    self.select_keymap(keycode.keymap_idx, time);
-                    let keycode = keycode.code;
                     match keycodes_count {
                         // Pressing a key made out of a single keycode is simple:
                         // press on press, release on release.
-                        1 => self.virtual_keyboard.switch(
-                            keycode,
-                            PressType::Pressed,
-                            time,
-                        ),
+                        1 => self.switch_key(keycode, PressType::Pressed, time),
                         // A key made of multiple keycodes
                         // has to submit them one after the other.
                         _ => {
-                            self.virtual_keyboard.switch(
-                                keycode.clone(),
-                                PressType::Pressed,
-                                time,
-                            );
-                            self.virtual_keyboard.switch(
-                                keycode.clone(),
-                                PressType::Released,
-                                time,
-                            );
+                            self.switch_key(keycode, PressType::Pressed, time);
+                            self.switch_key(keycode, PressType::Released, time);
                         },
                     };
                 }
@@ -187,6 +235,50 @@ impl Submission {
         self.pressed.push((key_id, submit_action));
     }
     
+    /// Presses and releases a key outside of any button presses.
+    pub fn tap_key(&mut self, keycode: &KeyCode, time: Timestamp) {
+        self.switch_key(keycode, PressType::Pressed, time);
+        self.switch_key(keycode, PressType::Released, time);
+    }
+
+    /// Submits text outside of any button presses.
+    /// Only possible via the input method.
+    pub fn type_string(&mut self, text: &CString)
+        -> Result<(), imservice::SubmitError>
+    {
+        match &mut self.imservice {
+            Some(imservice) => imservice.commit_string(text)
+                .and_then(|()| imservice.commit()),
+            None => Err(imservice::SubmitError::NotActive),
+        }
+    }
+
+    /// Types a whole word, like one decoded from a glide.
+    /// Only possible via the input method.
+    pub fn type_word(&mut self, text: &CString)
+        -> Result<(), imservice::SubmitError>
+    {
+        self.type_string(text)?;
+        self.record(Entry::Submitted {
+            action: Submitted::Text(text.to_string_lossy().into_owned()),
+        });
+        Ok(())
+    }
+
+    /// Replaces the text typed just before the cursor with another one,
+    /// like a glided word with one of the alternatives.
+    pub fn replace_text(&mut self, typed: &CString, replacement: &CString)
+        -> Result<(), imservice::SubmitError>
+    {
+        match &mut self.imservice {
+            Some(imservice) => imservice
+                .delete_surrounding_text(typed.as_bytes().len() as u32, 0)
+                .and_then(|()| imservice.commit_string(replacement))
+                .and_then(|()| imservice.commit()),
+            None => Err(imservice::SubmitError::NotActive),
+        }
+    }
+
     pub fn handle_release(&mut self, key_id: KeyStateId, time: Timestamp) {
         let index = self.pressed.iter().position(|(id, _)| *id == key_id);
         if let Some(index) = index {
@@ -199,15 +291,11 @@ impl Submission {
                 SubmittedAction::VirtualKeyboard(keycodes) => {
                     let keycodes_count = keycodes.len();
                     match keycodes_count {
-                        1 => {
-                            let keycode = &keycodes[0];
-                            self.select_keymap(keycode.keymap_idx, time);
-                            self.virtual_keyboard.switch(
-                                keycode.code,
-                                PressType::Released,
-                                time,
-                            );
-                        },
+                        1 => self.switch_key(
+                            &keycodes[0],
+                            PressType::Released,
+                            time,
+                        ),
                         // Design choice here: submit multiple all at press time
                         // and do nothing at release time.
                         _ => {},
@@ -243,6 +331,9 @@ impl Submission {
                 Modifier::Mod4 => Modifiers::MOD4,
             })
             .fold(Modifiers::empty(), |m, n| m | n);
+        self.record(Entry::Submitted {
+            action: Submitted::Modifiers(raw_modifiers.bits() as u32),
+        });
         self.virtual_keyboard.set_modifiers_state(raw_modifiers);
     }
 
@@ -266,6 +357,7 @@ impl Submission {
             return;
         }
         self.modifiers_active = Vec::new();
+        self.record(Entry::Submitted { action: Submitted::Modifiers(0) });
         self.virtual_keyboard.set_modifiers_state(Modifiers::empty())
     }
 
@@ -295,20 +387,17 @@ impl Submission {
     /// due to modifiers meaning different things in different keymaps.
     fn select_keymap(&mut self, idx: usize, time: Timestamp) {
         if self.keymap_idx != Some(idx) {
-            self.keymap_idx = Some(idx);
             self.clear_all_modifiers();
+            // Keys must be released in the keymap they were pressed in
             self.release_all_virtual_keys(time);
-            let keymap = &self.keymap_fds[idx];
-            self.virtual_keyboard.update_keymap(keymap);
+            self.keymap_idx = Some(idx);
+            self.virtual_keyboard.update_keymap(idx);
         }
     }
     
     pub fn use_layout(&mut self, layout: &layout::LayoutData, time: Timestamp) {
-        self.keymap_fds = layout.keymaps.iter()
-            .map(|keymap_str| vkeyboard::c::KeyMap::from_cstr(
-                keymap_str.as_c_str()
-            ))
-            .collect();
+        self.purpose = layout.purpose;
+        self.virtual_keyboard.set_keymaps(&layout.keymaps);
         self.keymap_idx = None;
 
         // This can probably be eliminated,
@@ -318,3 +407,400 @@ impl Submission {
         self.select_keymap(0, time);
     }
 }
+
+/// Submission backends keeping everything in memory.
+/// They stand in for Wayland in tests and when replaying recordings.
+pub mod memory {
+    use super::*;
+
+    use crate::imservice::{ ContentHint, SubmitError };
+    use std::cell::{ Cell, RefCell };
+    use std::rc::Rc;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Event {
+        Key { code: u32, pressed: bool },
+        Modifiers(Modifiers),
+        /// This many keymaps got loaded
+        Keymaps(usize),
+        /// The keymap with this index became current
+        Keymap(usize),
+        CommitString(String),
+        DeleteSurroundingText { before: u32, after: u32 },
+        Commit,
+    }
+
+    /// Events from all backends sharing it, in order
+    pub type Log = Rc<RefCell<Vec<Event>>>;
+
+    pub fn new_log() -> Log {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    pub struct VirtualKeyboard {
+        log: Log,
+    }
+
+    impl VirtualKeyboard {
+        pub fn new(log: Log) -> Self {
+            Self { log }
+        }
+    }
+
+    impl VirtualKeyboardSink for VirtualKeyboard {
+        fn switch(
+            &mut self,
+            keycode: u32,
+            action: PressType,
+            _timestamp: Timestamp,
+        ) {
+            self.log.borrow_mut().push(Event::Key {
+                code: keycode,
+                pressed: action == PressType::Pressed,
+            });
+        }
+
+        fn set_modifiers_state(&mut self, modifiers: Modifiers) {
+            self.log.borrow_mut().push(Event::Modifiers(modifiers));
+        }
+
+        fn set_keymaps(&mut self, keymaps: &[CString]) {
+            self.log.borrow_mut().push(Event::Keymaps(keymaps.len()));
+        }
+
+        fn update_keymap(&mut self, idx: usize) {
+            self.log.borrow_mut().push(Event::Keymap(idx));
+        }
+    }
+
+    /// The text field which the input method pretends to be attached to
+    #[derive(Clone, Copy, Debug)]
+    pub struct TextField {
+        pub purpose: ContentPurpose,
+        pub hint: ContentHint,
+    }
+
+    /// The focused text field, if any.
+    /// Shared, so that it can change while the input method is in use.
+    pub type Focus = Rc<Cell<Option<TextField>>>;
+
+    pub struct InputMethod {
+        log: Log,
+        focus: Focus,
+    }
+
+    impl InputMethod {
+        pub fn new(log: Log, focus: Focus) -> Self {
+            Self { log, focus }
+        }
+    }
+
+    impl InputMethodSink for InputMethod {
+        fn commit_string(&self, text: &CString) -> Result<(), SubmitError> {
+            match self.focus.get() {
+                Some(_) => {
+                    self.log.borrow_mut().push(Event::CommitString(
+                        text.to_string_lossy().into_owned()
+                    ));
+                    Ok(())
+                },
+                None => Err(SubmitError::NotActive),
+            }
+        }
+
+        fn commit(&mut self) -> Result<(), SubmitError> {
+            match self.focus.get() {
+                Some(_) => {
+                    self.log.borrow_mut().push(Event::Commit);
+                    Ok(())
+                },
+                None => Err(SubmitError::NotActive),
+            }
+        }
+
+        fn delete_surrounding_text(
+            &self,
+            before: u32, after: u32,
+        ) -> Result<(), SubmitError> {
+            match self.focus.get() {
+                Some(_) => {
+                    self.log.borrow_mut()
+                        .push(Event::DeleteSurroundingText { before, after });
+                    Ok(())
+                },
+                None => Err(SubmitError::NotActive),
+            }
+        }
+
+        fn is_active(&self) -> bool {
+            self.focus.get().is_some()
+        }
+
+        fn is_sensitive(&self) -> bool {
+            self.focus.get()
+                .map(|field| imservice::is_sensitive_field(field.purpose, field.hint))
+                .unwrap_or(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::memory::{ Event, TextField };
+    use crate::imservice::ContentHint;
+    use crate::layout::{ ArrangementKind, ButtonPosition, LayoutData, Margins };
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    fn make_submission(keymap_count: usize, field: Option<TextField>)
+        -> (Submission, memory::Log, memory::Focus)
+    {
+        let log = memory::new_log();
+        let focus = Rc::new(Cell::new(field));
+        let mut submission = Submission::new(
+            Box::new(memory::VirtualKeyboard::new(log.clone())),
+            Some(Box::new(memory::InputMethod::new(log.clone(), focus.clone()))),
+            None,
+        );
+        let layout = LayoutData {
+            margins: Margins { top: 0.0, bottom: 0.0, left: 0.0, right: 0.0 },
+            kind: ArrangementKind::Base,
+            purpose: ContentPurpose::Normal,
+            views: HashMap::new(),
+            keymaps: (0..keymap_count)
+                .map(|i| CString::new(format!("keymap {}", i)).unwrap())
+                .collect(),
+            cursor_keys: None,
+        };
+        submission.use_layout(&layout, Timestamp(0));
+        (submission, log, focus)
+    }
+
+    fn key_id(row: usize) -> KeyStateId {
+        (&ButtonPosition {
+            view: "base".into(),
+            row,
+            position_in_row: 0,
+        }).into()
+    }
+
+    fn keycode(code: u32, keymap_idx: usize) -> KeyCode {
+        KeyCode { code, keymap_idx }
+    }
+
+    const NORMAL: TextField = TextField {
+        purpose: ContentPurpose::Normal,
+        hint: ContentHint::NONE,
+    };
+
+    #[test]
+    fn keycodes_press_release() {
+        let (mut submission, log, _) = make_submission(1, None);
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Keycodes,
+            &vec![keycode(38, 0)],
+            Timestamp(1),
+        );
+        submission.handle_release(key_id(0), Timestamp(2));
+        assert_eq!(
+            log.borrow().clone(),
+            vec![
+                Event::Keymaps(1),
+                Event::Keymap(0),
+                Event::Key { code: 38, pressed: true },
+                Event::Key { code: 38, pressed: false },
+            ],
+        );
+    }
+
+    #[test]
+    fn multiple_keycodes_at_press() {
+        let (mut submission, log, _) = make_submission(1, None);
+        log.borrow_mut().clear();
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Keycodes,
+            &vec![keycode(10, 0), keycode(11, 0)],
+            Timestamp(1),
+        );
+        submission.handle_release(key_id(0), Timestamp(2));
+        assert_eq!(
+            log.borrow().clone(),
+            vec![
+                Event::Key { code: 10, pressed: true },
+                Event::Key { code: 10, pressed: false },
+                Event::Key { code: 11, pressed: true },
+                Event::Key { code: 11, pressed: false },
+            ],
+        );
+    }
+
+    #[test]
+    fn text_through_input_method() {
+        let (mut submission, log, _) = make_submission(1, Some(NORMAL));
+        log.borrow_mut().clear();
+        let text = CString::new("a").unwrap();
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Text(&text),
+            &vec![keycode(38, 0)],
+            Timestamp(1),
+        );
+        submission.handle_release(key_id(0), Timestamp(2));
+        assert_eq!(
+            log.borrow().clone(),
+            vec![Event::CommitString("a".into()), Event::Commit],
+        );
+    }
+
+    #[test]
+    fn replace_word() {
+        let (mut submission, log, _) = make_submission(1, Some(NORMAL));
+        log.borrow_mut().clear();
+        let typed = CString::new("café ").unwrap();
+        submission.type_word(&typed).unwrap();
+        submission.replace_text(&typed, &CString::new("cafe ").unwrap())
+            .unwrap();
+        assert_eq!(
+            log.borrow().clone(),
+            vec![
+                Event::CommitString("café ".into()),
+                Event::Commit,
+                Event::DeleteSurroundingText { before: 6, after: 0 },
+                Event::CommitString("cafe ".into()),
+                Event::Commit,
+            ],
+        );
+    }
+
+    #[test]
+    fn text_falls_back_to_keycodes() {
+        let (mut submission, log, _) = make_submission(1, None);
+        log.borrow_mut().clear();
+        let text = CString::new("a").unwrap();
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Text(&text),
+            &vec![keycode(38, 0)],
+            Timestamp(1),
+        );
+        assert_eq!(
+            log.borrow().clone(),
+            vec![Event::Key { code: 38, pressed: true }],
+        );
+    }
+
+    /// With a modifier on, text can't be committed
+    #[test]
+    fn modifier_forces_keycodes() {
+        let (mut submission, log, _) = make_submission(1, Some(NORMAL));
+        log.borrow_mut().clear();
+        submission.handle_add_modifier(key_id(1), Modifier::Control, Timestamp(1));
+        let text = CString::new("a").unwrap();
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Text(&text),
+            &vec![keycode(38, 0)],
+            Timestamp(2),
+        );
+        submission.handle_drop_modifier(key_id(1), Timestamp(3));
+        assert_eq!(
+            log.borrow().clone(),
+            vec![
+                Event::Modifiers(Modifiers::CONTROL),
+                Event::Key { code: 38, pressed: true },
+                Event::Modifiers(Modifiers::empty()),
+            ],
+        );
+    }
+
+    /// Held keys get released in their own keymap before it changes
+    #[test]
+    fn keymap_switch_releases_keys() {
+        let (mut submission, log, _) = make_submission(2, None);
+        log.borrow_mut().clear();
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Keycodes,
+            &vec![keycode(10, 0)],
+            Timestamp(1),
+        );
+        submission.handle_press(
+            key_id(1),
+            SubmitData::Keycodes,
+            &vec![keycode(20, 1)],
+            Timestamp(2),
+        );
+        // Already released
+        submission.handle_release(key_id(0), Timestamp(3));
+        submission.handle_release(key_id(1), Timestamp(4));
+        assert_eq!(
+            log.borrow().clone(),
+            vec![
+                Event::Key { code: 10, pressed: true },
+                Event::Key { code: 10, pressed: false },
+                Event::Keymap(1),
+                Event::Key { code: 20, pressed: true },
+                Event::Key { code: 20, pressed: false },
+            ],
+        );
+    }
+
+    #[test]
+    fn keymap_switch_clears_modifiers() {
+        let (mut submission, log, _) = make_submission(2, None);
+        log.borrow_mut().clear();
+        submission.handle_add_modifier(key_id(1), Modifier::Alt, Timestamp(1));
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Keycodes,
+            &vec![keycode(20, 1)],
+            Timestamp(2),
+        );
+        assert_eq!(
+            log.borrow().clone(),
+            vec![
+                Event::Modifiers(Modifiers::MOD1),
+                Event::Modifiers(Modifiers::empty()),
+                Event::Keymap(1),
+                Event::Key { code: 20, pressed: true },
+            ],
+        );
+        assert!(!submission.is_modifier_active(Modifier::Alt));
+    }
+
+    #[test]
+    fn sensitive_field_not_recorded() {
+        let (mut submission, _log, focus) = make_submission(1, Some(NORMAL));
+        let recorder = Recorder::in_memory();
+        submission.recorder = Some(recorder.clone());
+        let text = CString::new("a").unwrap();
+        submission.handle_press(
+            key_id(0),
+            SubmitData::Text(&text),
+            &vec![keycode(38, 0)],
+            Timestamp(1),
+        );
+        focus.set(Some(TextField {
+            purpose: ContentPurpose::Password,
+            hint: ContentHint::NONE,
+        }));
+        submission.handle_press(
+            key_id(1),
+            SubmitData::Text(&text),
+            &vec![keycode(38, 0)],
+            Timestamp(2),
+        );
+        assert_eq!(
+            recorder.get_entries(),
+            vec![
+                Entry::Submitted { action: Submitted::Text("a".into()) },
+                Entry::Redacted,
+            ],
+        );
+    }
+}
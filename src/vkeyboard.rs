@@ -1,7 +1,9 @@
 /*! Managing the events belonging to virtual-keyboard interface. */
 
 use crate::keyboard::{ Modifiers, PressType };
+use crate::logging;
 use crate::submission::Timestamp;
+use std::ffi::CString;
 
 /// Standard xkb keycode
 type KeyCode = u32;
@@ -69,13 +71,40 @@ pub mod c {
     }
 }
 
+/// Receives key events meant for the focused application
+pub trait VirtualKeyboardSink {
+    fn switch(
+        &mut self,
+        keycode: KeyCode,
+        action: PressType,
+        timestamp: Timestamp,
+    );
+    fn set_modifiers_state(&mut self, modifiers: Modifiers);
+    /// Replaces all available keymaps, without making any current
+    fn set_keymaps(&mut self, keymaps: &[CString]);
+    /// Makes the keymap with the given index current
+    fn update_keymap(&mut self, idx: usize);
+}
+
 /// Layout-independent backend. TODO: Have one instance per program or seat
-pub struct VirtualKeyboard(pub c::ZwpVirtualKeyboardV1);
+pub struct VirtualKeyboard {
+    vk: c::ZwpVirtualKeyboardV1,
+    keymaps: Vec<c::KeyMap>,
+}
 
 impl VirtualKeyboard {
+    pub fn new(vk: c::ZwpVirtualKeyboardV1) -> Self {
+        VirtualKeyboard {
+            vk,
+            keymaps: Vec::new(),
+        }
+    }
+}
+
+impl VirtualKeyboardSink for VirtualKeyboard {
     // TODO: error out if keymap not set
-    pub fn switch(
-        &self,
+    fn switch(
+        &mut self,
         keycode: KeyCode,
         action: PressType,
         timestamp: Timestamp,
@@ -83,24 +112,36 @@ impl VirtualKeyboard {
         let keycode = keycode - 8;
         unsafe {
             c::eek_virtual_keyboard_v1_key(
-                self.0, timestamp.0, keycode, action.clone() as u32
+                self.vk, timestamp.0, keycode, action.clone() as u32
             );
         }
     }
     
-    pub fn set_modifiers_state(&self, modifiers: Modifiers) {
+    fn set_modifiers_state(&mut self, modifiers: Modifiers) {
         let modifiers = modifiers.bits() as u32;
         unsafe {
-            c::eek_virtual_keyboard_set_modifiers(self.0, modifiers);
+            c::eek_virtual_keyboard_set_modifiers(self.vk, modifiers);
         }
     }
-    
-    pub fn update_keymap(&self, keymap: &c::KeyMap) {
-        unsafe {
-            c::eek_virtual_keyboard_update_keymap(
-                self.0,
-                keymap as *const c::KeyMap,
-            );
+
+    fn set_keymaps(&mut self, keymaps: &[CString]) {
+        self.keymaps = keymaps.iter()
+            .map(|keymap_str| c::KeyMap::from_cstr(keymap_str.as_c_str()))
+            .collect();
+    }
+
+    fn update_keymap(&mut self, idx: usize) {
+        match self.keymaps.get(idx) {
+            Some(keymap) => unsafe {
+                c::eek_virtual_keyboard_update_keymap(
+                    self.vk,
+                    keymap as *const c::KeyMap,
+                );
+            },
+            None => log_print!(
+                logging::Level::Bug,
+                "No keymap with index {}", idx,
+            ),
         }
     }
 }
@@ -6,6 +6,7 @@
 #include "wlr-layer-shell-unstable-v1-client-protocol.h"
 #include "virtual-keyboard-unstable-v1-client-protocol.h"
 #include "input-method-unstable-v2-client-protocol.h"
+#include "fractional-scale-v1-client-protocol.h"
 
 #include "outputs.h"
 
@@ -16,6 +17,8 @@ struct squeek_wayland {
     struct zwp_input_method_manager_v2 *input_method_manager;
     struct squeek_outputs *outputs;
     struct wl_seat *seat;
+    /// Optional
+    struct wp_fractional_scale_manager_v1 *fractional_scale_manager;
     // objects
     struct zwp_input_method_v2 *input_method;
     struct zwp_virtual_keyboard_v1 *virtual_keyboard;
@@ -53,6 +53,21 @@ fn data_dir() -> Option<PathBuf> {
         .or_else(|| home_dir().map(|h| h.join(".local/share")))
 }
 
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .and_then(is_absolute_path)
+        .or_else(|| home_dir().map(|h| h.join(".config")))
+}
+
+/// Returns the path to the file within the config dir
+pub fn config_path<P>(path: P) -> Option<PathBuf>
+    where P: AsRef<Path>
+{
+    config_dir().map(|dir| {
+        dir.join(path.as_ref())
+    })
+}
+
 /// Returns the path to the directory within the data dir
 pub fn data_path<P>(path: P) -> Option<PathBuf>
     where P: AsRef<Path>